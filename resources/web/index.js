@@ -17,6 +17,25 @@ const Utils = {
             return `${(seconds / 60).toFixed(1)}min`
         }
     },
+    formatWorkDuration(seconds) {
+        const hours = Math.floor(seconds / 3600)
+        const minutes = Math.round((seconds % 3600) / 60)
+        return [hours && `${hours}h`, minutes && `${minutes}m`].filter(Boolean).join(' ') || '0m'
+    },
+    // Read the error answered by the server, even when it is not the expected JSON, like a proxy's
+    // error page
+    async errorBody(response) {
+        const text = await response.text()
+        try {
+            return JSON.parse(text)
+        } catch (error) {
+            return {
+                message: text.trim() || `Call failed with status ${response.status}`,
+                jira_messages: [],
+                field_errors: {},
+            }
+        }
+    },
 }
 
 const appComponent = Vue.createApp({
@@ -25,31 +44,96 @@ const appComponent = Vue.createApp({
         return {
             loaded: false,
             loading: false,
+            updateAgain: false,
             lastUpdate: new Date,
             name: null,
+            quickFilters: [],
+            activeQuickFilters: [],
+            searchText: '',
+            sprints: [],
+            sprintId: null,
+            // The sprint chosen by the user, instead of the active one
+            selectedSprintId: null,
             columns: [],
+            swimlanes: [],
+            moveError: null,
+            showBacklog: false,
         }
     },
+    computed: {
+        sprint() {
+            return this.sprints.find(sprint => sprint.id === this.sprintId) || null
+        },
+        issuesByKey() {
+            const issues = {}
+            for (const column of this.columns) {
+                for (const issue of column.issues) {
+                    issues[issue.key] = issue
+                }
+            }
+            return issues
+        },
+    },
     methods: {
         ...Utils,
         async update() {
             if (this.loading) {
+                this.updateAgain = true
                 return
             }
 
             this.loading = true
             try {
-                const response = await (await fetch('/api/board')).json()
+                // The assignee and the epic can be given in the page's URL
+                const pageParams = new URLSearchParams(window.location.search)
+                const searchParams = new URLSearchParams({
+                    quick_filters: this.activeQuickFilters.join(','),
+                    text: this.searchText,
+                    assignee: pageParams.get('assignee') || '',
+                    epic: pageParams.get('epic') || '',
+                })
+                if (this.selectedSprintId !== null) {
+                    searchParams.set('sprint', this.selectedSprintId)
+                }
+                const response = await (await fetch(`/api/board?${searchParams}`)).json()
                 this.name = response.name
+                this.quickFilters = response.quick_filters
+                this.sprints = response.sprints
+                this.sprintId = response.sprint_id
                 this.columns = response.columns
+                this.swimlanes = response.swimlanes
 
                 await this.$refs.issueDetails.update()
+                if (this.showBacklog) {
+                    await this.$refs.backlog.update()
+                }
 
                 this.loaded = true
                 this.lastUpdate = new Date
             } finally {
                 this.loading = false
             }
+
+            if (this.updateAgain) {
+                this.updateAgain = false
+                await this.update()
+            }
+        },
+        toggleBacklog() {
+            this.showBacklog = !this.showBacklog
+        },
+        selectSprint(event) {
+            this.selectedSprintId = Number(event.target.value)
+            this.update().catch(console.error)
+        },
+        toggleQuickFilter(name) {
+            const index = this.activeQuickFilters.indexOf(name)
+            if (index === -1) {
+                this.activeQuickFilters.push(name)
+            } else {
+                this.activeQuickFilters.splice(index, 1)
+            }
+            this.update().catch(console.error)
         },
         openIssue(key) {
             this.$refs.issueDetails.open(key)
@@ -59,7 +143,38 @@ const appComponent = Vue.createApp({
         },
         startEdit(key) {
             this.$refs.issueEditor.edit(key)
-        }
+        },
+        async moveIssue(key, columnName) {
+            const source = this.columns.find(column => column.issues.some(issue => issue.key === key))
+            const target = this.columns.find(column => column.name === columnName)
+            if (!source || !target || source === target) {
+                return
+            }
+
+            // Move the card right away, putting it back if Jira refuses the transition
+            const index = source.issues.findIndex(issue => issue.key === key)
+            const [issue] = source.issues.splice(index, 1)
+            target.issues.unshift(issue)
+            this.moveError = null
+
+            try {
+                const response = await fetch(`/api/issue/${key}/move`, {
+                    method: 'POST',
+                    headers: {'Content-Type': 'application/json'},
+                    body: JSON.stringify({column: columnName}),
+                })
+                if (!response.ok) {
+                    const body = await Utils.errorBody(response)
+                    throw new Error(body.jira_messages.length ? body.jira_messages.join(' ') : body.message)
+                }
+            } catch (error) {
+                target.issues.splice(target.issues.indexOf(issue), 1)
+                source.issues.splice(index, 0, issue)
+                this.moveError = `Failed to move ${key}: ${error.message}`
+            }
+
+            await this.update()
+        },
     }
 })
 
@@ -75,12 +190,27 @@ appComponent.component('issue-details', {
             // Details
             avatars: null,
             branches: null,
+            builds: null,
+            comments: [],
+            deployments: null,
             description: null,
             epic: null,
             jiraLink: null,
+            links: [],
             mergeRequests: null,
+            numCommits: null,
             status: null,
+            subtasksDone: 0,
+            subtasksTotal: 0,
             summary: null,
+            timeRemainingSeconds: null,
+            timeSpentSeconds: null,
+            // Comments
+            commentError: null,
+            editingCommentBody: '',
+            editingCommentId: null,
+            newComment: '',
+            savingComment: false,
         }
     },
     mounted() {
@@ -91,12 +221,52 @@ appComponent.component('issue-details', {
         })
     },
     methods: {
+        ...Utils,
         open(key) {
             this.modal.show()
             this.issueKey = key
             this.loaded = false
+            this.comments = []
+            this.commentError = null
+            this.editingCommentId = null
+            this.newComment = ''
             this.update().catch(console.error)
         },
+        startCommentEdit(comment) {
+            this.editingCommentId = comment.id
+            this.editingCommentBody = comment.body
+        },
+        async saveComment(id, body) {
+            const key = this.issueKey
+            const url = id === null ? `/api/issue/${key}/comments` : `/api/issue/${key}/comments/${id}`
+
+            this.savingComment = true
+            this.commentError = null
+            try {
+                const response = await fetch(url, {method: 'POST', body})
+                if (!response.ok) {
+                    const body = await Utils.errorBody(response)
+                    throw new Error(body.jira_messages.length ? body.jira_messages.join(' ') : body.message)
+                }
+                if (id === null) {
+                    this.newComment = ''
+                } else {
+                    this.editingCommentId = null
+                }
+                await this.updateComments()
+            } catch (error) {
+                this.commentError = String(error)
+            } finally {
+                this.savingComment = false
+            }
+        },
+        async updateComments() {
+            const key = this.issueKey
+            const response = await (await fetch(`/api/issue/${key}/comments`)).json()
+            if (key === this.issueKey) {
+                this.comments = response
+            }
+        },
         startEdit() {
             this.$emit('editIssue', this.issueKey)
             this.modal.hide()
@@ -112,13 +282,23 @@ appComponent.component('issue-details', {
                 this.loaded = true
                 this.avatars = response.avatars
                 this.branches = response.branches
+                this.builds = response.builds
+                this.deployments = response.deployments
                 this.description = response.description
                 this.epic = response.epic
                 this.jiraLink = response.jira_link
+                this.links = response.links
                 this.mergeRequests = response.merge_requests
+                this.numCommits = response.num_commits
                 this.status = response.status
+                this.subtasksDone = response.subtasks_done
+                this.subtasksTotal = response.subtasks_total
                 this.summary = response.summary
+                this.timeRemainingSeconds = response.time_remaining_seconds
+                this.timeSpentSeconds = response.time_spent_seconds
             }
+
+            await this.updateComments()
         },
     }
 })
@@ -173,7 +353,7 @@ appComponent.component('issue-editor', {
             this.issueKey = key
             this.saving = false
 
-            fetch(`/api/edit-issue-code/${key}`).then(response => response.text()).then(issueCode => {
+            fetch(`/api/edit-issue-code/${key}?comments=true`).then(response => response.text()).then(issueCode => {
                 if (this.issueKey === key) {
                     this.editor.setValue(issueCode, -1)
                     this.editor.setReadOnly(false)
@@ -193,8 +373,14 @@ appComponent.component('issue-editor', {
             try {
                 const response = await fetch(url, {method: 'POST', body: code})
                 if (!response.ok) {
-                    const body = await response.text()
-                    throw new Error(`Call failed with status ${response.status}:\n${body}`)
+                    const body = await Utils.errorBody(response)
+                    const fieldErrors = Object.entries(body.field_errors)
+                        .map(([field, message]) => `${field}: ${message}`)
+                    const explanations = [...body.jira_messages, ...fieldErrors]
+                    throw new Error([
+                        `Call failed with status ${response.status}:`,
+                        ...(explanations.length ? explanations : [body.message]),
+                    ].join('\n'))
                 }
                 this.modal.hide()
                 this.$emit('issue-created')
@@ -244,13 +430,108 @@ appComponent.component('relative-date', {
     }
 })
 
+appComponent.component('board-backlog', {
+    props: ['sprints', 'columns'],
+    template: '#board-backlog',
+    data() {
+        return {
+            loaded: false,
+            issues: [],
+            moreIssues: 0,
+            selectedKeys: [],
+            targetSprintId: '',
+            targetColumn: '',
+            dropTargetKey: null,
+            moving: false,
+            error: null,
+        }
+    },
+    mounted() {
+        this.update().catch(console.error)
+    },
+    methods: {
+        async update() {
+            const response = await (await fetch('/api/board/backlog')).json()
+            this.issues = response.issues
+            this.moreIssues = response.more_issues
+            const keys = this.issues.map(issue => issue.key)
+            this.selectedKeys = this.selectedKeys.filter(key => keys.includes(key))
+            this.loaded = true
+        },
+        toggleSelection(key) {
+            const index = this.selectedKeys.indexOf(key)
+            if (index === -1) {
+                this.selectedKeys.push(key)
+            } else {
+                this.selectedKeys.splice(index, 1)
+            }
+        },
+        async drop(event, targetKey) {
+            this.dropTargetKey = null
+            const key = event.dataTransfer.getData('text/plain')
+            if (!key || key === targetKey) {
+                return
+            }
+
+            // Dragging a selected issue ranks the whole selection, keeping its order
+            const keys = this.selectedKeys.includes(key)
+                ? this.issues.map(issue => issue.key).filter(key => this.selectedKeys.includes(key) && key !== targetKey)
+                : [key]
+            await this.request('/api/issues/rank', {issues: keys, before: targetKey})
+        },
+        async moveSelected() {
+            await this.request('/api/issues/move', {
+                issues: this.selectedKeys,
+                sprint: this.targetSprintId === '' ? null : Number(this.targetSprintId),
+                column: this.targetColumn || null,
+            })
+            this.$emit('issuesMoved')
+        },
+        async request(url, body) {
+            this.moving = true
+            this.error = null
+            try {
+                const response = await fetch(url, {
+                    method: 'POST',
+                    headers: {'Content-Type': 'application/json'},
+                    body: JSON.stringify(body),
+                })
+                if (!response.ok) {
+                    const body = await Utils.errorBody(response)
+                    throw new Error(body.jira_messages.length ? body.jira_messages.join(' ') : body.message)
+                }
+            } catch (error) {
+                this.error = error.message
+            } finally {
+                this.moving = false
+            }
+            await this.update()
+        },
+    },
+})
+
 appComponent.component('board-column', {
-    props: ['name', 'issues', 'isLast', 'statusIds'],
+    props: ['name', 'issues', 'moreIssues', 'isLast', 'statusIds', 'hideHeader'],
     template: '#board-column',
+    data() {
+        return {
+            dropTarget: false,
+        }
+    },
+    methods: {
+        drop(event) {
+            this.dropTarget = false
+            const key = event.dataTransfer.getData('text/plain')
+            if (key) {
+                this.$emit('moveIssue', key, this.name)
+            }
+        },
+    },
 })
 
 appComponent.component('board-issue', {
-    props: ['issueKey', 'summary', 'status', 'avatars', 'epic', 'branches', 'mergeRequests', 'isFlagged'],
+    props: ['issueKey', 'summary', 'status', 'avatars', 'epic', 'branches', 'mergeRequests', 'isFlagged',
+        'timeSpentSeconds', 'timeRemainingSeconds', 'subtasksDone', 'subtasksTotal', 'isBlocked'],
     template: '#board-issue',
     methods: {
         ...Utils,
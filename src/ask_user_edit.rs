@@ -7,6 +7,40 @@ use anyhow::{ensure, Context, Result};
 use directories::ProjectDirs;
 use time::OffsetDateTime;
 
+/// Ask the user to edit the template until `parse` accepts it. The parsing errors are shown at the
+/// top of the file on each new attempt. Return `None` if the user gives the template back unchanged
+/// or empty, to abandon the process
+pub fn ask_user_edit_until<T>(
+    project_dirs: &ProjectDirs,
+    template: &str,
+    extension: &str,
+    mut parse: impl FnMut(&str) -> Result<T>,
+) -> Result<Option<T>> {
+    let mut contents = template.to_string();
+    loop {
+        contents = ask_user_edit(project_dirs, &contents, extension)?;
+
+        if contents.trim() == template.trim() || contents.trim().is_empty() {
+            return Ok(None);
+        }
+
+        match parse(&contents) {
+            Err(error) => {
+                contents = format!(
+                    "-- Failed to parse: {:#}\n\
+                    -- Please edit it to fix the problem\n\
+                    -- If you want to abandon the process, provide an empty file\n\
+                    {}",
+                    error, contents
+                );
+
+                tracing::warn!("Failed to parse: {}. Please retry", error);
+            }
+            Ok(parsed) => return Ok(Some(parsed)),
+        }
+    }
+}
+
 pub fn ask_user_edit(
     project_dirs: &ProjectDirs,
     contents: &str,
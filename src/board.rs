@@ -1,42 +1,103 @@
-use crate::config::{BoardLocalConfig, Config};
+use crate::config::{
+    BoardLocalConfig, Config, DevelopmentInfoMode, DevelopmentInfoProviders, IssueFieldConfig,
+    SwimlanesConfig,
+};
+use crate::forge_api::{ForgeApi, MergeRequestStatus};
+use crate::issue_code;
+use crate::jira_api::{Sprint, Transition};
 use crate::local_jira_cache::LocalJiraCache;
-use anyhow::{Context, Result};
+use anyhow::{ensure, Context, Result};
 use futures::future;
 use itertools::Itertools;
 use serde::{Deserialize, Serialize};
 use serde_json::Value;
-use std::collections::BTreeSet;
+use std::collections::{BTreeMap, BTreeSet};
 use std::fmt::Write;
 use std::sync::Arc;
+use time::{Date, Month, OffsetDateTime};
 
 #[derive(Debug)]
 pub struct Board {
     cached_api: Arc<LocalJiraCache>,
+    config: Arc<Config>,
     api_host: String,
     local_config: BoardLocalConfig,
+    development_info_providers: DevelopmentInfoProviders,
+    forge_api: Arc<ForgeApi>,
+    /// The declared field used to group the issues in swimlanes, if any
+    swimlane_field: Option<IssueFieldConfig>,
 }
 
 #[derive(Debug, Clone)]
 struct BoardJiraConfig {
     columns: Vec<Column>,
     name: String,
+    is_scrum: bool,
 }
 
 #[derive(Debug, Clone, Serialize)]
 pub struct BoardData {
     name: String,
+    /// The names of the quick filters that can be applied
+    quick_filters: Vec<String>,
+    /// The active and future sprints, if this is a scrum board
+    sprints: Vec<BoardSprintData>,
+    /// The sprint whose issues are displayed, if any
+    sprint_id: Option<u64>,
     columns: Vec<BoardColumnData>,
+    /// Empty if the board is not grouped in swimlanes
+    swimlanes: Vec<BoardSwimlaneData>,
+}
+
+/// Conditions to narrow the issues displayed in the board
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct BoardFilter {
+    /// The names of the quick filters to apply, separated by commas
+    quick_filters: Option<String>,
+    /// An account id, or "me" for the current user
+    assignee: Option<String>,
+    /// The key of the epic
+    epic: Option<String>,
+    /// Free text to search for
+    text: Option<String>,
+    /// The id of the sprint to show, instead of the active one
+    sprint: Option<u64>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct BoardSprintData {
+    id: u64,
+    name: String,
+    state: String,
+    goal: Option<String>,
+    start_date: Option<String>,
+    end_date: Option<String>,
+    /// How many days until the end of the sprint, if it has started
+    remaining_days: Option<i64>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct BoardSwimlaneData {
+    name: String,
+    color: Option<String>,
+    image: Option<String>,
+    /// The keys of the issues in this lane, for each column
+    issue_keys: Vec<Vec<String>>,
 }
 
 #[derive(Debug, Clone, Serialize)]
 pub struct BoardColumnData {
     name: String,
     issues: Vec<BoardIssueData>,
+    /// How many issues in this column were not loaded, because of `max_cards_per_column`
+    more_issues: usize,
     status_ids: Vec<String>,
 }
 
 #[derive(Debug, Clone, Serialize)]
 pub struct BoardIssueData {
+    #[serde(skip)]
+    id: String,
     key: String,
     jira_link: String,
     summary: String,
@@ -46,7 +107,23 @@ pub struct BoardIssueData {
     epic: Option<BoardEpicData>,
     branches: Vec<BoardBranch>,
     merge_requests: Vec<BoardMergeRequest>,
+    num_commits: usize,
+    builds: Vec<BoardBuild>,
+    deployments: Vec<BoardDeployment>,
+    /// The total time logged, in seconds
+    time_spent_seconds: Option<u64>,
+    /// The remaining estimate, in seconds
+    time_remaining_seconds: Option<u64>,
     is_flagged: bool,
+    /// How many subtasks are done
+    subtasks_done: usize,
+    subtasks_total: usize,
+    links: Vec<BoardIssueLink>,
+    /// Whether some issue that is not done yet blocks this one
+    is_blocked: bool,
+    /// The values of the field used to group the issues in swimlanes, if any
+    #[serde(skip)]
+    swimlane_values: Vec<String>,
 }
 
 #[derive(Debug, Clone, Serialize, Ord, PartialOrd, Eq, PartialEq)]
@@ -55,6 +132,16 @@ pub struct BoardAvatarData {
     image: String,
 }
 
+#[derive(Debug, Clone, Serialize)]
+pub struct BoardIssueLink {
+    /// How this issue relates to the linked one, like "is blocked by"
+    relation: String,
+    key: String,
+    summary: String,
+    status: String,
+    is_done: bool,
+}
+
 #[derive(Debug, Clone, Serialize)]
 pub struct BoardEpicData {
     key: String,
@@ -74,6 +161,43 @@ pub struct BoardMergeRequest {
     name: String,
     status: String,
     url: String,
+    /// The state of the build of the merge request's branch, if known
+    build_state: Option<String>,
+    /// The live status, if the merge request is hosted in a configured forge
+    forge_status: Option<MergeRequestStatus>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct BoardBacklogData {
+    /// Ordered by rank
+    issues: Vec<BoardIssueData>,
+    /// How many issues in the backlog were not loaded, because of `max_cards_per_column`
+    more_issues: usize,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct BoardComment {
+    id: String,
+    author: String,
+    avatar: Option<String>,
+    body: String,
+    created: String,
+    updated: String,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct BoardBuild {
+    name: String,
+    state: String,
+    url: String,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct BoardDeployment {
+    name: String,
+    environment: String,
+    state: String,
+    url: String,
 }
 
 #[derive(Debug, Clone)]
@@ -84,7 +208,7 @@ struct Column {
 
 impl Board {
     pub async fn open(
-        config: &Config,
+        config: Arc<Config>,
         cached_api: Arc<LocalJiraCache>,
         board_name: &str,
     ) -> Result<Self> {
@@ -100,50 +224,269 @@ impl Board {
             })?
             .clone();
 
+        let development_info_providers = board
+            .development_info_providers
+            .clone()
+            .unwrap_or_else(|| config.development_info_providers.clone());
+
+        let swimlane_field = match &board.swimlanes {
+            Some(SwimlanesConfig::Field { field }) => {
+                let issue_field = config
+                    .issue_fields
+                    .iter()
+                    .find(|issue_field| &issue_field.name == field)
+                    .with_context(|| format!("Swimlane field {} is not declared", field))?;
+                ensure!(
+                    issue_code::jira_field_name(issue_field).is_some(),
+                    "Swimlane field {} is not an issue field",
+                    field
+                );
+                Some(issue_field.clone())
+            }
+            _ => None,
+        };
+
         Ok(Board {
             cached_api,
             api_host: config.api_host.clone(),
             local_config: board,
+            development_info_providers,
+            forge_api: Arc::new(ForgeApi::new(&config)),
+            swimlane_field,
+            config,
         })
     }
 
-    pub async fn load(&self) -> Result<BoardData> {
-        // Determine which fields are needed
+    pub async fn load(&self, filter: &BoardFilter) -> Result<BoardData> {
+        let fields = self.request_fields();
+        let jira_config = self.jira_config().await?;
+
+        // Scrum boards show a single sprint, the active one by default
+        let sprints = if jira_config.is_scrum {
+            self.cached_api
+                .sprints(self.local_config.board_id.clone())
+                .await?
+        } else {
+            vec![]
+        };
+        let sprint_id = filter.sprint.or_else(|| {
+            sprints
+                .iter()
+                .find(|sprint| sprint.state == "active")
+                .map(|sprint| sprint.id)
+        });
+
+        let filter_jql = self.filter_jql(filter, sprint_id)?;
+
+        // Load all columns
+        let num_columns = jira_config.columns.len();
+        let columns = future::try_join_all(jira_config.columns.into_iter().enumerate().map(
+            |(i, column)| {
+                self.load_column(
+                    fields.clone(),
+                    column,
+                    i == num_columns - 1,
+                    filter_jql.as_deref(),
+                )
+            },
+        ))
+        .await?;
+
+        let swimlanes = match &self.local_config.swimlanes {
+            None => vec![],
+            Some(swimlanes) => group_swimlanes(swimlanes, &columns),
+        };
+
+        let sprints = sprints
+            .into_iter()
+            .map(|sprint| BoardSprintData {
+                remaining_days: match (sprint.state.as_str(), &sprint.end_date) {
+                    ("active", Some(end_date)) => days_until(end_date),
+                    _ => None,
+                },
+                id: sprint.id,
+                name: sprint.name,
+                state: sprint.state,
+                goal: sprint.goal.filter(|goal| !goal.is_empty()),
+                start_date: sprint.start_date,
+                end_date: sprint.end_date,
+            })
+            .collect();
+
+        Ok(BoardData {
+            name: jira_config.name,
+            sprints,
+            sprint_id,
+            quick_filters: self
+                .local_config
+                .quick_filters
+                .iter()
+                .map(|quick_filter| quick_filter.name.clone())
+                .collect(),
+            columns,
+            swimlanes,
+        })
+    }
+
+    /// Load the issues in the backlog, ordered by rank
+    pub async fn backlog(&self) -> Result<BoardBacklogData> {
+        let response = self
+            .cached_api
+            .backlog_issues(
+                self.local_config.board_id.clone(),
+                self.request_fields(),
+                self.local_config.max_cards_per_column,
+            )
+            .await?;
+        let more_issues = response.total.saturating_sub(response.issues.len());
+
+        let issues = future::try_join_all(
+            response
+                .issues
+                .into_iter()
+                .map(|issue| self.load_issue(issue.id, issue.key, issue.fields)),
+        )
+        .await?;
+
+        Ok(BoardBacklogData {
+            issues,
+            more_issues,
+        })
+    }
+
+    /// The fields of the issues needed to display the cards, separated by commas
+    fn request_fields(&self) -> String {
         let mut request_fields = BTreeSet::new();
         request_fields.insert("status");
         request_fields.insert("summary");
         request_fields.insert("parent");
+        request_fields.insert("timespent");
+        request_fields.insert("timeestimate");
+        request_fields.insert("subtasks");
+        request_fields.insert("issuelinks");
         for card_avatar in &self.local_config.card_avatars {
             request_fields.insert(card_avatar);
         }
         if let Some(flag) = &self.local_config.flag {
             request_fields.insert(flag);
         }
-        let fields = request_fields.into_iter().join(",");
+        if let Some(swimlane_field) = self
+            .swimlane_field
+            .as_ref()
+            .and_then(issue_code::jira_field_name)
+        {
+            request_fields.insert(swimlane_field);
+        }
+        request_fields.into_iter().join(",")
+    }
 
+    pub async fn issue(&self, key: String) -> Result<BoardIssueData> {
+        let data = self.cached_api.issue(key).await?;
+
+        let mut issue = self.load_issue(data.id, data.key, data.fields).await?;
+        if self.local_config.development_info != DevelopmentInfoMode::Disabled {
+            self.load_development_infos(vec![&mut issue]).await;
+        }
+
+        Ok(issue)
+    }
+
+    /// Return the transitions that can be executed from the current status of the issue
+    pub async fn transitions(&self, key: String) -> Result<Vec<Transition>> {
+        let status_id = self.status_id(key.clone()).await?;
+        self.cached_api.transitions(key, status_id).await
+    }
+
+    /// Find the transition that moves the issue to one of the statuses of the given column. Return
+    /// `None` if the issue is already there
+    pub async fn column_transition(
+        &self,
+        key: String,
+        column_name: &str,
+    ) -> Result<Option<Transition>> {
         let jira_config = self.jira_config().await?;
+        let column = jira_config
+            .columns
+            .iter()
+            .find(|column| column.name == column_name)
+            .with_context(|| format!("Column {} not found in the board", column_name))?;
 
-        // Load all columns
+        let status_id = self.status_id(key.clone()).await?;
+        if column.status_ids.contains(&status_id) {
+            return Ok(None);
+        }
+
+        let transition = self
+            .cached_api
+            .transitions(key, status_id)
+            .await?
+            .into_iter()
+            .find(|transition| column.status_ids.contains(&transition.to.id))
+            .with_context(|| {
+                format!(
+                    "No transition from the current status leads to the column {}",
+                    column_name
+                )
+            })?;
+
+        ensure!(
+            transition
+                .fields
+                .values()
+                .all(|field| !field.required || field.has_default_value),
+            "The transition {} has required fields, so the issue must be edited instead",
+            transition.name
+        );
+
+        Ok(Some(transition))
+    }
+
+    pub async fn comments(&self, key: String) -> Result<Vec<BoardComment>> {
+        let comments = self.cached_api.comments(key).await?;
+
+        Ok(comments
+            .into_iter()
+            .map(|comment| BoardComment {
+                id: comment.id,
+                author: comment.author.display_name,
+                avatar: comment.author.avatar_urls.map(|urls| urls.size_32),
+                body: comment.body,
+                created: comment.created,
+                updated: comment.updated,
+            })
+            .collect())
+    }
+
+    /// Drop the cached data affected by a change to the given issue. The columns loaded with a
+    /// filter are always dropped, since the change can make the issue match it. If the issue moved
+    /// to a new status, the columns that can now show it are also dropped.
+    pub async fn invalidate_issue(&self, key: &str, to_status_id: Option<&str>) -> Result<()> {
+        let jira_config = self.jira_config().await?;
         let num_columns = jira_config.columns.len();
-        let columns = future::try_join_all(
-            jira_config
-                .columns
-                .into_iter()
-                .enumerate()
-                .map(|(i, column)| self.load_column(fields.clone(), column, i == num_columns - 1)),
-        )
-        .await?;
+        let mut jqls = vec![];
+        for (i, column) in jira_config.columns.iter().enumerate() {
+            let column_jql = self.column_jql(column, i == num_columns - 1)?;
+            // The prefix of the column queries with a filter, see `load_column()`
+            jqls.push(format!("{} and ", column_jql));
+            if to_status_id.map_or(false, |to_status_id| {
+                column.status_ids.iter().any(|id| id == to_status_id)
+            }) {
+                jqls.push(column_jql);
+            }
+        }
 
-        Ok(BoardData {
-            name: jira_config.name,
-            columns,
-        })
+        self.cached_api.invalidate_issue(key, &jqls);
+
+        Ok(())
     }
 
-    pub async fn issue(&self, key: String) -> Result<BoardIssueData> {
-        let data = self.cached_api.issue(key).await?;
+    async fn status_id(&self, key: String) -> Result<String> {
+        let issue = self.cached_api.issue(key).await?;
+        let status_id = issue.fields["status"]["id"]
+            .as_str()
+            .context("Could not extract status id")?;
 
-        self.load_issue(data.id, data.key, data.fields).await
+        Ok(status_id.to_owned())
     }
 
     async fn jira_config(&self) -> Result<BoardJiraConfig> {
@@ -180,6 +523,7 @@ impl Board {
         Ok(BoardJiraConfig {
             columns,
             name: jira_data.name,
+            is_scrum: jira_data.kind.as_deref() == Some("scrum"),
         })
     }
 
@@ -188,20 +532,24 @@ impl Board {
         fields: String,
         column: Column,
         is_last: bool,
+        filter_jql: Option<&str>,
     ) -> Result<BoardColumnData> {
-        let mut jql = format!("status in ({})", column.status_ids.iter().format(","));
-        if let (true, Some(filter_resolved)) =
-            (is_last, &self.local_config.filter_last_column_resolved)
-        {
-            write!(jql, " and resolved >= {:?}", filter_resolved)?;
+        let mut jql = self.column_jql(&column, is_last)?;
+        if let Some(filter_jql) = filter_jql {
+            write!(jql, " and {}", filter_jql)?;
         }
-
         let response = self
             .cached_api
-            .board_issues(self.local_config.board_id.clone(), fields, jql)
+            .board_issues(
+                self.local_config.board_id.clone(),
+                fields,
+                jql,
+                self.local_config.max_cards_per_column,
+            )
             .await?;
+        let more_issues = response.total.saturating_sub(response.issues.len());
 
-        let issues = future::try_join_all(
+        let mut issues = future::try_join_all(
             response
                 .issues
                 .into_iter()
@@ -209,13 +557,96 @@ impl Board {
         )
         .await?;
 
+        // Other columns keep loading in the meantime
+        if self.local_config.development_info == DevelopmentInfoMode::Eager {
+            self.load_development_infos(issues.iter_mut().collect_vec())
+                .await;
+        }
+
         Ok(BoardColumnData {
             name: column.name,
             issues,
+            more_issues,
             status_ids: column.status_ids,
         })
     }
 
+    /// Return the active and future sprints, if this is a scrum board
+    pub async fn sprints(&self) -> Result<Vec<Sprint>> {
+        if !self.jira_config().await?.is_scrum {
+            return Ok(vec![]);
+        }
+
+        self.cached_api
+            .sprints(self.local_config.board_id.clone())
+            .await
+    }
+
+    /// Find the active or future sprint with the given name
+    pub async fn sprint_id(&self, name: &str) -> Result<u64> {
+        let sprints = self.sprints().await?;
+
+        Ok(issue_code::find_sprint(&sprints, name)?.id)
+    }
+
+    /// Combine all conditions of the filter in a single JQL condition, if any
+    fn filter_jql(&self, filter: &BoardFilter, sprint_id: Option<u64>) -> Result<Option<String>> {
+        let mut conditions = vec![];
+
+        if let Some(sprint_id) = sprint_id {
+            conditions.push(format!("sprint = {}", sprint_id));
+        }
+
+        let quick_filter_names = filter
+            .quick_filters
+            .iter()
+            .flat_map(|names| names.split(','))
+            .map(str::trim)
+            .filter(|name| !name.is_empty());
+        for name in quick_filter_names {
+            let quick_filter = self
+                .local_config
+                .quick_filters
+                .iter()
+                .find(|quick_filter| quick_filter.name == name)
+                .with_context(|| format!("Quick filter {} is not known", name))?;
+            conditions.push(format!("({})", quick_filter.jql));
+        }
+
+        match filter.assignee.as_deref() {
+            None | Some("") => {}
+            Some("me") => conditions.push("assignee = currentUser()".to_owned()),
+            Some(assignee) => conditions.push(format!("assignee = {:?}", assignee)),
+        }
+        if let Some(epic) = filter.epic.as_deref().filter(|epic| !epic.is_empty()) {
+            conditions.push(format!("parent = {:?}", epic));
+        }
+        if let Some(text) = filter
+            .text
+            .as_deref()
+            .filter(|text| !text.trim().is_empty())
+        {
+            conditions.push(format!("text ~ {:?}", text.trim()));
+        }
+
+        if conditions.is_empty() {
+            Ok(None)
+        } else {
+            Ok(Some(conditions.join(" and ")))
+        }
+    }
+
+    fn column_jql(&self, column: &Column, is_last: bool) -> Result<String> {
+        let mut jql = format!("status in ({})", column.status_ids.iter().format(","));
+        if let (true, Some(filter_resolved)) =
+            (is_last, &self.local_config.filter_last_column_resolved)
+        {
+            write!(jql, " and resolved >= {:?}", filter_resolved)?;
+        }
+
+        Ok(jql)
+    }
+
     async fn load_issue(&self, id: String, key: String, fields: Value) -> Result<BoardIssueData> {
         #[derive(Debug, Deserialize)]
         #[serde(rename_all = "camelCase")]
@@ -230,6 +661,52 @@ impl Board {
             size_32: String,
         }
 
+        #[derive(Debug, Deserialize)]
+        #[serde(rename_all = "camelCase")]
+        struct IssueLink {
+            #[serde(rename = "type")]
+            kind: IssueLinkType,
+            inward_issue: Option<LinkedIssue>,
+            outward_issue: Option<LinkedIssue>,
+        }
+
+        #[derive(Debug, Deserialize)]
+        struct IssueLinkType {
+            name: String,
+            inward: String,
+            outward: String,
+        }
+
+        #[derive(Debug, Deserialize)]
+        struct LinkedIssue {
+            key: String,
+            fields: LinkedIssueFields,
+        }
+
+        #[derive(Debug, Deserialize)]
+        struct LinkedIssueFields {
+            summary: String,
+            status: LinkedIssueStatus,
+        }
+
+        #[derive(Debug, Deserialize)]
+        #[serde(rename_all = "camelCase")]
+        struct LinkedIssueStatus {
+            name: String,
+            status_category: StatusCategory,
+        }
+
+        #[derive(Debug, Deserialize)]
+        struct StatusCategory {
+            key: String,
+        }
+
+        impl LinkedIssue {
+            fn is_done(&self) -> bool {
+                self.fields.status.status_category.key == "done"
+            }
+        }
+
         let summary = fields["summary"]
             .as_str()
             .context("Could not extract summary field")?
@@ -270,55 +747,179 @@ impl Board {
             Some(key) => Some(self.load_epic(key.to_string()).await?),
         };
 
-        let (branches, merge_requests) = self.load_development_info(id).await;
-
         let is_flagged = match self.local_config.flag.as_ref() {
             None => false,
             Some(field) => !fields.get(field).unwrap_or(&Value::Null).is_null(),
         };
 
+        // A card is still useful without its subtasks and links
+        let subtasks: Vec<LinkedIssue> = match fields.get("subtasks") {
+            None | Some(Value::Null) => vec![],
+            Some(value) => serde_json::from_value(value.clone()).unwrap_or_else(|error| {
+                tracing::warn!("Failed to read subtasks of {}: {}", key, error);
+                vec![]
+            }),
+        };
+
+        let issue_links: Vec<IssueLink> = match fields.get("issuelinks") {
+            None | Some(Value::Null) => vec![],
+            Some(value) => serde_json::from_value(value.clone()).unwrap_or_else(|error| {
+                tracing::warn!("Failed to read issue links of {}: {}", key, error);
+                vec![]
+            }),
+        };
+        let mut is_blocked = false;
+        let mut links = vec![];
+        for issue_link in issue_links {
+            // The inward issues relate to this one with the inward description, like "is blocked by"
+            let (relation, linked_issue) = match (issue_link.inward_issue, issue_link.outward_issue)
+            {
+                (Some(linked_issue), _) => {
+                    is_blocked |= issue_link.kind.name == "Blocks" && !linked_issue.is_done();
+                    (issue_link.kind.inward, linked_issue)
+                }
+                (None, Some(linked_issue)) => (issue_link.kind.outward, linked_issue),
+                (None, None) => continue,
+            };
+            links.push(BoardIssueLink {
+                relation,
+                is_done: linked_issue.is_done(),
+                key: linked_issue.key,
+                summary: linked_issue.fields.summary,
+                status: linked_issue.fields.status.name,
+            });
+        }
+
+        let swimlane_values = match &self.swimlane_field {
+            None => vec![],
+            Some(swimlane_field) => {
+                issue_code::kaiju_field_values(&self.config, swimlane_field, &fields)
+                    .unwrap_or_else(|error| {
+                        tracing::warn!("Failed to read swimlane field of {}: {}", key, error);
+                        vec![]
+                    })
+            }
+        };
+
         Ok(BoardIssueData {
             jira_link: format!("{}/browse/{}", self.api_host, key),
+            id,
             key,
             summary,
             description,
             status,
             avatars: avatars.into_iter().collect(),
             epic,
-            branches,
-            merge_requests,
+            branches: vec![],
+            merge_requests: vec![],
+            num_commits: 0,
+            builds: vec![],
+            deployments: vec![],
+            time_spent_seconds: fields["timespent"].as_u64(),
+            time_remaining_seconds: fields["timeestimate"].as_u64(),
             is_flagged,
+            subtasks_done: subtasks.iter().filter(|subtask| subtask.is_done()).count(),
+            subtasks_total: subtasks.len(),
+            links,
+            is_blocked,
+            swimlane_values,
         })
     }
 
-    async fn load_development_info(
-        &self,
-        issue_id: String,
-    ) -> (Vec<BoardBranch>, Vec<BoardMergeRequest>) {
-        match self.cached_api.development_info(issue_id).await {
-            Ok(info) => {
-                let branches = info
-                    .branches
-                    .into_iter()
-                    .map(|branch| BoardBranch {
-                        name: branch.name,
-                        url: branch.url,
-                    })
-                    .collect_vec();
-                let merge_requests = info
-                    .merge_requests
-                    .into_iter()
-                    .map(|merge_request| BoardMergeRequest {
-                        name: merge_request.name,
-                        status: merge_request.status,
-                        url: merge_request.url,
-                    })
-                    .collect_vec();
-                (branches, merge_requests)
+    /// Load the development info of all the given issues concurrently, one request per issue
+    async fn load_development_infos(&self, mut issues: Vec<&mut BoardIssueData>) {
+        let issue_ids = issues.iter().map(|issue| issue.id.clone()).collect_vec();
+        let mut infos = self
+            .cached_api
+            .development_infos(issue_ids, &self.development_info_providers)
+            .await;
+
+        for issue in &mut issues {
+            match infos.remove(&issue.id) {
+                None => {}
+                Some(Ok(info)) => {
+                    issue.branches = info
+                        .branches
+                        .into_iter()
+                        .map(|branch| BoardBranch {
+                            name: branch.name,
+                            url: branch.url,
+                        })
+                        .collect_vec();
+                    issue.merge_requests = info
+                        .merge_requests
+                        .into_iter()
+                        .map(|merge_request| {
+                            let build_state = merge_request.source.and_then(|source| {
+                                info.builds
+                                    .iter()
+                                    .find(|build| {
+                                        build.references.iter().any(|reference| {
+                                            reference.git_ref.as_ref().map(|git_ref| &git_ref.name)
+                                                == Some(&source.branch)
+                                        })
+                                    })
+                                    .map(|build| build.state.clone())
+                            });
+
+                            BoardMergeRequest {
+                                name: merge_request.name,
+                                status: merge_request.status,
+                                url: merge_request.url,
+                                build_state,
+                                forge_status: None,
+                            }
+                        })
+                        .collect_vec();
+                    issue.num_commits = info
+                        .repositories
+                        .iter()
+                        .map(|repository| repository.commits.len())
+                        .sum();
+                    issue.builds = info
+                        .builds
+                        .into_iter()
+                        .map(|build| BoardBuild {
+                            name: build.name,
+                            state: build.state,
+                            url: build.url,
+                        })
+                        .collect_vec();
+                    issue.deployments = info
+                        .deployments
+                        .into_iter()
+                        .map(|deployment| BoardDeployment {
+                            name: deployment.display_name,
+                            environment: deployment.environment.display_name,
+                            state: deployment.state,
+                            url: deployment.url,
+                        })
+                        .collect_vec();
+                }
+                Some(Err(error)) => {
+                    tracing::warn!("Failed to load development info: {:#}", error);
+                }
             }
-            Err(error) => {
-                tracing::warn!("Failed to load development info: {}", error);
-                (vec![], vec![])
+        }
+
+        let merge_requests = issues
+            .iter_mut()
+            .flat_map(|issue| issue.merge_requests.iter_mut())
+            .filter(|merge_request| self.forge_api.knows(&merge_request.url))
+            .collect_vec();
+        let statuses = future::join_all(merge_requests.iter().map(|merge_request| {
+            self.cached_api
+                .merge_request_status(self.forge_api.clone(), merge_request.url.clone())
+        }))
+        .await;
+        for (merge_request, status) in merge_requests.into_iter().zip(statuses) {
+            match status {
+                Ok(status) => merge_request.forge_status = Some(status),
+                Err(error) => tracing::warn!(
+                    "Failed to load status of merge request {}: {:#}",
+                    merge_request.url,
+                    error
+                ),
             }
         }
     }
@@ -375,3 +976,67 @@ impl Board {
         }
     }
 }
+
+/// Group the issues of the columns in lanes, sorted by name, with the issues that do not belong to
+/// any lane at the end. Issues that belong to many lanes appear in each of them
+fn group_swimlanes(
+    swimlanes: &SwimlanesConfig,
+    columns: &[BoardColumnData],
+) -> Vec<BoardSwimlaneData> {
+    const OTHERS: &str = "Others";
+
+    // Sort by whether it is the lane of the others, then by name
+    let mut lanes: BTreeMap<(bool, String), BoardSwimlaneData> = BTreeMap::new();
+    for (i, column) in columns.iter().enumerate() {
+        for issue in &column.issues {
+            let mut issue_lanes = match swimlanes {
+                SwimlanesConfig::Epic => issue
+                    .epic
+                    .iter()
+                    .map(|epic| (epic.short_name.clone(), epic.color.clone(), None))
+                    .collect_vec(),
+                SwimlanesConfig::Avatar => issue
+                    .avatars
+                    .iter()
+                    .map(|avatar| (avatar.name.clone(), None, Some(avatar.image.clone())))
+                    .collect_vec(),
+                SwimlanesConfig::Field { .. } => issue
+                    .swimlane_values
+                    .iter()
+                    .unique()
+                    .map(|value| (value.clone(), None, None))
+                    .collect_vec(),
+            };
+            let is_others = issue_lanes.is_empty();
+            if is_others {
+                issue_lanes.push((OTHERS.to_owned(), None, None));
+            }
+
+            for (name, color, image) in issue_lanes {
+                lanes
+                    .entry((is_others, name.clone()))
+                    .or_insert_with(|| BoardSwimlaneData {
+                        name,
+                        color,
+                        image,
+                        issue_keys: vec![vec![]; columns.len()],
+                    })
+                    .issue_keys[i]
+                    .push(issue.key.clone());
+            }
+        }
+    }
+
+    lanes.into_values().collect()
+}
+
+/// The number of days from today until the given date, like "2023-05-01T10:00:00.000Z"
+fn days_until(date: &str) -> Option<i64> {
+    let mut parts = date.get(..10)?.split('-');
+    let year = parts.next()?.parse().ok()?;
+    let month = Month::try_from(parts.next()?.parse::<u8>().ok()?).ok()?;
+    let day = parts.next()?.parse().ok()?;
+    let date = Date::from_calendar_date(year, month, day).ok()?;
+
+    Some((date - OffsetDateTime::now_utc().date()).whole_days())
+}
@@ -0,0 +1,69 @@
+use anyhow::{Context, Result};
+use directories::ProjectDirs;
+
+use crate::config::Config;
+use crate::local_jira_cache::CacheStats;
+
+pub async fn cache_stats(project_dirs: &ProjectDirs) -> Result<()> {
+    let config: Config = Config::new(project_dirs)?;
+
+    let url = format!("http://localhost:{}/api/debug/cache", config.server_port);
+    let stats: CacheStats = reqwest::get(&url)
+        .await
+        .and_then(|response| response.error_for_status())
+        .with_context(|| format!("Could not reach a running board at {}", url))?
+        .json()
+        .await?;
+
+    println!(
+        "{} entries, approximately {:.1} MB",
+        stats.num_entries,
+        stats.size_bytes as f64 / 1e6
+    );
+    println!(
+        "Parallelism of {}, with {} permits available and {} loads waiting for one",
+        stats.parallelism, stats.available_permits, stats.waiting_for_permit
+    );
+    println!();
+
+    println!(
+        "{:<20} {:>8} {:>8} {:>8} {:>10} {:>8} {:>8} {:>10} {:>12}",
+        "kind",
+        "entries",
+        "hits",
+        "stale",
+        "coalesced",
+        "misses",
+        "errors",
+        "in flight",
+        "latency (ms)"
+    );
+    for (kind, kind_stats) in &stats.kinds {
+        let latency = kind_stats
+            .average_latency_ms
+            .map(|latency| format!("{:.0}", latency))
+            .unwrap_or_else(|| "-".to_owned());
+        println!(
+            "{:<20} {:>8} {:>8} {:>8} {:>10} {:>8} {:>8} {:>10} {:>12}",
+            kind,
+            kind_stats.entries,
+            kind_stats.hits,
+            kind_stats.stale_hits,
+            kind_stats.coalesced,
+            kind_stats.misses,
+            kind_stats.errors,
+            kind_stats.in_flight,
+            latency
+        );
+    }
+
+    if !stats.loading.is_empty() {
+        println!();
+        println!("Loading:");
+        for key in &stats.loading {
+            println!("- {}", key);
+        }
+    }
+
+    Ok(())
+}
@@ -1,48 +1,221 @@
-use anyhow::Result;
+use anyhow::{ensure, Context, Result};
+use clap::{Args, ValueEnum};
 use directories::ProjectDirs;
+use serde_json::{json, Value};
+use std::io::Read;
+use std::path::PathBuf;
+use std::{fs, io, mem};
 
-use crate::ask_user_edit::ask_user_edit;
+use crate::ask_user_edit::ask_user_edit_until;
 use crate::config::Config;
 use crate::issue_code;
-use crate::issue_code::{parse_issue_markdown, prepare_api_body};
-use crate::jira_api::JiraApi;
+use crate::issue_code::{parse_issue_markdown, prepare_api_body, CreateIssue};
+use crate::jira_api::{JiraApi, NewIssueLink};
 
-pub async fn create_issue(project_dirs: &ProjectDirs) -> Result<()> {
+#[derive(Debug, Args)]
+pub struct CreateIssueOptions {
+    /// Read the issue code from this file, instead of writing it in the editor
+    #[clap(long, value_name = "PATH", conflicts_with = "stdin")]
+    from_file: Option<PathBuf>,
+    /// Read the issue code from the standard input, instead of writing it in the editor
+    #[clap(long)]
+    stdin: bool,
+    /// The summary of the issue. Without any issue code, the other fields get their default values
+    /// and the editor is not opened
+    #[clap(long)]
+    summary: Option<String>,
+    /// The description of the issue, replacing the one in the issue code
+    #[clap(long)]
+    description: Option<String>,
+    /// Set the values of a Kaiju command, like "Type=Bug". Can be repeated
+    #[clap(long = "field", value_name = "NAME=VALUE")]
+    fields: Vec<String>,
+    /// Print the body that would be sent to Jira, without creating the issue
+    #[clap(long)]
+    dry_run: bool,
+    /// How to print the created issue
+    #[clap(long, value_enum, default_value_t = OutputFormat::Text)]
+    output: OutputFormat,
+}
+
+#[derive(Debug, Clone, Copy, Eq, PartialEq, ValueEnum)]
+pub enum OutputFormat {
+    Text,
+    /// The key and the URL of the created issue, in a JSON object
+    Json,
+}
+
+/// An issue ready to be created, with what can only be added once it exists
+#[derive(Debug)]
+pub struct PreparedIssue {
+    pub summary: String,
+    pub body: Value,
+    pub links: Vec<NewIssueLink>,
+    pub subtasks: Vec<String>,
+}
+
+impl PreparedIssue {
+    pub fn new(config: &Config, mut issue: CreateIssue) -> Result<Self> {
+        Ok(PreparedIssue {
+            summary: issue.summary.clone(),
+            links: mem::take(&mut issue.links),
+            subtasks: mem::take(&mut issue.subtasks),
+            body: prepare_api_body(config, issue)?,
+        })
+    }
+}
+
+pub async fn create_issue(project_dirs: &ProjectDirs, options: CreateIssueOptions) -> Result<()> {
     let config: Config = Config::new(project_dirs)?;
 
-    let template = issue_code::new_issue(&config, None)?;
-    let mut issue_markdown = template.clone();
-    let api_body = loop {
-        issue_markdown = ask_user_edit(project_dirs, &issue_markdown, "md")?;
+    let given_markdown = if let Some(path) = &options.from_file {
+        Some(
+            fs::read_to_string(path)
+                .with_context(|| format!("Could not read {}", path.display()))?,
+        )
+    } else if options.stdin {
+        let mut markdown = String::new();
+        io::stdin().read_to_string(&mut markdown)?;
+        Some(markdown)
+    } else {
+        None
+    };
 
-        if issue_markdown.trim() == template.trim() || issue_markdown.trim().is_empty() {
-            tracing::warn!("Exiting because the user does not want to create the issue");
-            return Ok(());
+    let prepared = match given_markdown {
+        Some(markdown) => prepare_issue(&config, &markdown, &options, false)?,
+        None if options.summary.is_some() => {
+            let template = issue_code::new_issue(&config, None)?;
+            prepare_issue(&config, &template, &options, true)?
         }
-
-        let maybe_api_body = parse_issue_markdown(&issue_markdown)
-            .and_then(|issue| prepare_api_body(&config, issue));
-        match maybe_api_body {
-            Err(error) => {
-                issue_markdown = format!(
-                    "-- Failed to parse issue: {:#}\n\
-                    -- Please edit it to fix the problem\n\
-                    -- If you want to abandon the process, provide an empty file\n\
-                    {}",
-                    error, issue_markdown
-                );
-
-                tracing::warn!("Failed to parse issue: {}. Please retry", error);
+        None => {
+            let template = issue_code::new_issue(&config, None)?;
+            let prepared = ask_user_edit_until(project_dirs, &template, "md", |markdown| {
+                prepare_issue(&config, markdown, &options, false)
+            })?;
+            match prepared {
+                Some(prepared) => prepared,
+                None => {
+                    tracing::warn!("Exiting because the user does not want to create the issue");
+                    return Ok(());
+                }
             }
-            Ok(api_body) => break api_body,
         }
     };
 
+    if options.dry_run {
+        println!("{}", serde_json::to_string_pretty(&prepared.body)?);
+        if !prepared.links.is_empty() {
+            tracing::info!("Would also create the links {:?}", prepared.links);
+        }
+        if !prepared.subtasks.is_empty() {
+            tracing::info!("Would also create the subtasks {:?}", prepared.subtasks);
+        }
+        return Ok(());
+    }
+
     tracing::info!("Will request Jira API");
     let api = JiraApi::new(&config);
-    let key = api.create_issue(&api_body).await?;
+    let key = api.create_issue(&prepared.body).await?;
+
+    let url = format!("{}/browse/{}", config.api_host, key);
+    match options.output {
+        OutputFormat::Text => tracing::info!("Created issue: {}", url),
+        OutputFormat::Json => println!("{}", json!({ "key": key, "url": url })),
+    }
+
+    api.link_issues_and_create_subtasks(&key, &prepared.links, &prepared.subtasks)
+        .await
+}
+
+/// Parse the issue code, overridden by the options, and prepare it to be created. The placeholder
+/// description of the template is dropped.
+fn prepare_issue(
+    config: &Config,
+    markdown: &str,
+    options: &CreateIssueOptions,
+    is_template: bool,
+) -> Result<PreparedIssue> {
+    let mut issue = parse_issue_markdown(markdown)?;
+    ensure!(
+        issue.sprint.is_none(),
+        "The Sprint command is only supported in the board"
+    );
+
+    if let Some(summary) = &options.summary {
+        issue.summary = summary.clone();
+    }
+    if let Some(description) = &options.description {
+        issue.description = description.clone();
+    } else if is_template {
+        issue.description = String::new();
+    }
+    for field in &options.fields {
+        issue.set_command(field)?;
+    }
+
+    PreparedIssue::new(config, issue)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn options(summary: Option<&str>, description: Option<&str>) -> CreateIssueOptions {
+        CreateIssueOptions {
+            from_file: None,
+            stdin: false,
+            summary: summary.map(str::to_string),
+            description: description.map(str::to_string),
+            fields: vec![],
+            dry_run: false,
+            output: OutputFormat::Text,
+        }
+    }
+
+    fn config() -> Config {
+        let mut config: Config =
+            toml::from_str(include_str!("../../resources/default_config.toml")).unwrap();
+        config.issue_fields.clear();
+        config
+    }
+
+    #[test]
+    fn test_prepare_issue_from_template() {
+        let config = config();
+        let template = issue_code::new_issue(&config, None).unwrap();
+
+        let prepared =
+            prepare_issue(&config, &template, &options(Some("Fix login"), None), true).unwrap();
+        assert_eq!(prepared.summary, "Fix login");
+        assert_eq!(prepared.body["fields"]["summary"], "Fix login");
+        assert_eq!(prepared.body["fields"]["description"], "");
+
+        let prepared = prepare_issue(
+            &config,
+            &template,
+            &options(Some("Fix login"), Some("It fails")),
+            true,
+        )
+        .unwrap();
+        assert_eq!(prepared.body["fields"]["description"], "It fails");
+    }
+
+    #[test]
+    fn test_prepare_issue_description_override() {
+        let config = config();
+        let markdown = "# Summary\n\nOld description\n\n# Kaiju\n";
 
-    tracing::info!("Created issue: {}/browse/{}", config.api_host, key);
+        let prepared = prepare_issue(&config, markdown, &options(None, None), false).unwrap();
+        assert_eq!(prepared.body["fields"]["description"], "Old description");
 
-    Ok(())
+        let prepared = prepare_issue(
+            &config,
+            markdown,
+            &options(None, Some("New description")),
+            false,
+        )
+        .unwrap();
+        assert_eq!(prepared.summary, "Summary");
+        assert_eq!(prepared.body["fields"]["description"], "New description");
+    }
 }
@@ -0,0 +1,141 @@
+use std::fs;
+use std::path::Path;
+
+use anyhow::{ensure, Context, Result};
+use directories::ProjectDirs;
+use itertools::Itertools;
+use serde::Serialize;
+
+use crate::ask_user_edit::ask_user_edit_until;
+use crate::commands::create_issue::PreparedIssue;
+use crate::config::Config;
+use crate::issue_code;
+use crate::issue_code::parse_issue_markdown;
+use crate::jira_api::JiraApi;
+
+/// The outcome of creating one of the issues of a document
+#[derive(Debug, Clone, Serialize)]
+pub struct BulkIssueResult {
+    pub summary: String,
+    pub key: Option<String>,
+    pub error: Option<String>,
+}
+
+pub async fn create_issues(project_dirs: &ProjectDirs, file: Option<&Path>) -> Result<()> {
+    let config: Config = Config::new(project_dirs)?;
+
+    let issues = match file {
+        Some(file) => {
+            let markdown = fs::read_to_string(file)
+                .with_context(|| format!("Could not read {}", file.display()))?;
+            prepare_issues(&config, &markdown)?
+        }
+        None => {
+            let template = issue_code::new_issues(&config)?;
+            let issues = ask_user_edit_until(project_dirs, &template, "md", |markdown| {
+                prepare_issues(&config, markdown)
+            })?;
+            match issues {
+                Some(issues) => issues,
+                None => {
+                    tracing::warn!("Exiting because the user does not want to create the issues");
+                    return Ok(());
+                }
+            }
+        }
+    };
+
+    tracing::info!("Will request Jira API");
+    let api = JiraApi::new(&config);
+    let results = create_prepared_issues(&api, &config, issues).await?;
+
+    println!("{:<12} {:<50} error", "key", "summary");
+    for result in &results {
+        println!(
+            "{:<12} {:<50} {}",
+            result.key.as_deref().unwrap_or("-"),
+            result.summary,
+            result.error.as_deref().unwrap_or("")
+        );
+    }
+
+    let num_failed = results.iter().filter(|result| result.key.is_none()).count();
+    ensure!(
+        num_failed == 0,
+        "Failed to create {} of {} issues",
+        num_failed,
+        results.len()
+    );
+
+    Ok(())
+}
+
+/// Parse all issues of the document and prepare their API bodies, failing if any of them is
+/// invalid, so that none is created
+pub fn prepare_issues(config: &Config, markdown: &str) -> Result<Vec<PreparedIssue>> {
+    let documents = issue_code::split_issues_markdown(markdown);
+    ensure!(!documents.is_empty(), "No issue was found");
+
+    let mut issues = vec![];
+    let mut errors = vec![];
+    for (index, document) in documents.iter().enumerate() {
+        let prepared = parse_issue_markdown(document).and_then(|issue| {
+            ensure!(
+                issue.sprint.is_none() && issue.new_comment.is_none() && issue.subtasks.is_empty(),
+                "Sprints, comments and subtasks are not supported when creating many issues"
+            );
+            PreparedIssue::new(config, issue)
+        });
+        match prepared {
+            Ok(issue) => issues.push(issue),
+            Err(error) => errors.push(format!("issue {}: {:#}", index + 1, error)),
+        }
+    }
+
+    ensure!(
+        errors.is_empty(),
+        "{} of {} issues are invalid: {}",
+        errors.len(),
+        documents.len(),
+        errors.join("; ")
+    );
+
+    Ok(issues)
+}
+
+/// Create the prepared issues with Jira's bulk endpoint, reporting the outcome of each one. The
+/// links of each created issue are added afterwards
+pub async fn create_prepared_issues(
+    api: &JiraApi,
+    config: &Config,
+    issues: Vec<PreparedIssue>,
+) -> Result<Vec<BulkIssueResult>> {
+    let bodies = issues.iter().map(|issue| issue.body.clone()).collect_vec();
+    let created = api.create_issues(&bodies).await?;
+
+    let mut results = vec![];
+    for (issue, created) in issues.into_iter().zip(created) {
+        let result = match created {
+            Ok(key) => {
+                tracing::info!("Created issue: {}/browse/{}", config.api_host, key);
+                let links_result = api.link_issues(&key, &issue.links).await;
+                BulkIssueResult {
+                    summary: issue.summary,
+                    key: Some(key),
+                    error: links_result.err().map(|error| format!("{:#}", error)),
+                }
+            }
+            Err(error) => BulkIssueResult {
+                summary: issue.summary,
+                key: None,
+                error: Some(format!(
+                    "{:#}",
+                    issue_code::with_kaiju_field_names(config, error.into())
+                )),
+            },
+        };
+        results.push(result);
+    }
+
+    Ok(results)
+}
@@ -0,0 +1,203 @@
+use anyhow::{Context, Result};
+use directories::ProjectDirs;
+use itertools::Itertools;
+use serde_json::{Map, Value};
+use std::mem;
+
+use crate::ask_user_edit::ask_user_edit_until;
+use crate::config::Config;
+use crate::issue_code;
+use crate::issue_code::{parse_issue_markdown, prepare_api_body};
+use crate::jira_api::{JiraApi, NewIssueLink, Sprint, Transition};
+
+/// The changes to apply to an issue, parsed from its code. Both the board and the `edit-issue`
+/// command go through it
+#[derive(Debug)]
+pub struct IssueChanges {
+    /// The transition to execute, with the fields of its screen
+    pub transition: Option<(Transition, Map<String, Value>)>,
+    pub body: Value,
+    pub new_comment: Option<String>,
+    pub sprint_id: Option<u64>,
+    pub links: Vec<NewIssueLink>,
+    pub subtasks: Vec<String>,
+}
+
+impl IssueChanges {
+    /// Parse the issue code. The transitions are the ones available for the issue and the sprints
+    /// are the ones of its board, if any
+    pub fn parse(
+        config: &Config,
+        markdown: &str,
+        transitions: &[Transition],
+        sprints: &[Sprint],
+    ) -> Result<Self> {
+        let mut info = parse_issue_markdown(markdown).context("Failed to parse Markdown")?;
+        let new_comment = info.new_comment.take();
+        let links = mem::take(&mut info.links);
+        let subtasks = mem::take(&mut info.subtasks);
+
+        let sprint_id = match info.sprint.take() {
+            None => None,
+            Some(sprint) => Some(issue_code::find_sprint(sprints, &sprint)?.id),
+        };
+
+        let transition = match info.transition.take() {
+            None => None,
+            Some(transition_name) => {
+                let transition =
+                    issue_code::find_transition(config, transitions, &transition_name)?;
+                let fields =
+                    issue_code::take_transition_fields(config, transition, &mut info.commands)?;
+                Some((transition.clone(), fields))
+            }
+        };
+
+        let body = prepare_api_body(config, info).context("Failed to prepare Jira API call")?;
+
+        Ok(IssueChanges {
+            transition,
+            body,
+            new_comment,
+            sprint_id,
+            links,
+            subtasks,
+        })
+    }
+
+    /// The status the issue is moved to, if any
+    pub fn to_status_id(&self) -> Option<&str> {
+        self.transition
+            .as_ref()
+            .map(|(transition, _)| transition.to.id.as_str())
+    }
+
+    /// Apply the changes, in order. The links and the subtasks are both attempted, even if one of
+    /// them fails
+    pub async fn apply(&self, config: &Config, api: &JiraApi, key: &str) -> Result<()> {
+        tracing::info!("Will request Jira API");
+        if let Some((transition, fields)) = &self.transition {
+            api.transition_issue(key, &transition.id, fields).await?;
+        }
+        api.edit_issue(key, &self.body)
+            .await
+            .map_err(|error| issue_code::with_kaiju_field_names(config, error))?;
+        if let Some(new_comment) = &self.new_comment {
+            api.add_comment(key, new_comment).await?;
+        }
+        if let Some(sprint_id) = self.sprint_id {
+            api.move_issues_to_sprint(sprint_id, &[key.to_string()])
+                .await?;
+        }
+
+        api.link_issues_and_create_subtasks(key, &self.links, &self.subtasks)
+            .await
+    }
+}
+
+pub async fn edit_issue(
+    project_dirs: &ProjectDirs,
+    key: &str,
+    board_name: Option<&str>,
+) -> Result<()> {
+    let config: Config = Config::new(project_dirs)?;
+    let api = JiraApi::new(&config);
+
+    tracing::info!("Will request Jira API");
+    let issue = api.issue(key).await?;
+    let transitions = api.transitions(key).await?;
+    let comments = api.comments(key).await?;
+    let sprints = match board_name {
+        None => vec![],
+        Some(board_name) => {
+            let board = config.board.get(board_name).with_context(|| {
+                format!(
+                    "Board '{}' not found in the config. Valid names are: {}",
+                    board_name,
+                    config.board.keys().format(", ")
+                )
+            })?;
+            api.sprints(&board.board_id).await?
+        }
+    };
+
+    let template = issue_code::edit_issue(
+        &config,
+        issue.fields,
+        &transitions,
+        &sprints,
+        Some(&comments),
+    )?;
+    let changes = ask_user_edit_until(project_dirs, &template, "md", |markdown| {
+        IssueChanges::parse(&config, markdown, &transitions, &sprints)
+    })?;
+    let changes = match changes {
+        Some(changes) => changes,
+        None => {
+            tracing::warn!("Exiting because the user does not want to edit the issue");
+            return Ok(());
+        }
+    };
+
+    changes.apply(&config, &api, key).await?;
+    tracing::info!("Edited issue: {}/browse/{}", config.api_host, key);
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_parse_changes() {
+        let mut config: Config =
+            toml::from_str(include_str!("../../resources/default_config.toml")).unwrap();
+        config.issue_fields.clear();
+        let transitions = serde_json::from_value::<Vec<Transition>>(json!([{
+            "id": "40",
+            "name": "Close",
+            "to": {"id": "4", "name": "Done"},
+            "fields": {
+                "resolution": {
+                    "name": "Resolution",
+                    "required": true,
+                    "allowedValues": [{"id": "1", "name": "Fixed"}],
+                },
+            },
+        }]))
+        .unwrap();
+        let sprints = serde_json::from_value::<Vec<Sprint>>(json!([
+            {"id": 41, "name": "Sprint 41", "state": "active"},
+            {"id": 42, "name": "Sprint 42", "state": "future"},
+        ]))
+        .unwrap();
+        let markdown = "# Summary
+# Kaiju
+Transition: Close
+Resolution: Fixed
+Sprint: Sprint 42
+Blocks: WEB-12
+## Subtasks
+- [ ] Write migration
+";
+
+        let changes = IssueChanges::parse(&config, markdown, &transitions, &sprints).unwrap();
+        let (transition, fields) = changes.transition.as_ref().unwrap();
+        assert_eq!(transition.id, "40");
+        assert_eq!(
+            Value::Object(fields.clone()),
+            json!({"resolution": {"id": "1"}})
+        );
+        assert_eq!(changes.to_status_id(), Some("4"));
+        assert_eq!(changes.sprint_id, Some(42));
+        assert_eq!(changes.links.len(), 1);
+        assert_eq!(changes.subtasks, ["Write migration"]);
+        assert_eq!(changes.body["fields"]["summary"], "Summary");
+        assert!(changes.body["fields"].get("resolution").is_none());
+
+        // Without a board, there is no sprint to move the issue to
+        assert!(IssueChanges::parse(&config, markdown, &transitions, &[]).is_err());
+    }
+}
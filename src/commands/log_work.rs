@@ -0,0 +1,33 @@
+use anyhow::Result;
+use directories::ProjectDirs;
+
+use crate::config::Config;
+use crate::issue_code::parse_duration;
+use crate::jira_api::{JiraApi, WorkLog};
+
+pub async fn log_work(
+    project_dirs: &ProjectDirs,
+    key: &str,
+    duration: &str,
+    comment: Option<String>,
+) -> Result<()> {
+    let config: Config = Config::new(project_dirs)?;
+
+    let work_log = WorkLog {
+        time_spent: parse_duration(duration)?,
+        comment,
+    };
+
+    tracing::info!("Will request Jira API");
+    let api = JiraApi::new(&config);
+    api.add_work_log(key, &work_log).await?;
+
+    tracing::info!(
+        "Logged {} in {}/browse/{}",
+        work_log.time_spent,
+        config.api_host,
+        key
+    );
+
+    Ok(())
+}
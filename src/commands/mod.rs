@@ -1,3 +1,7 @@
+pub mod cache_stats;
 pub mod create_issue;
+pub mod create_issues;
 pub mod edit_config;
+pub mod edit_issue;
+pub mod log_work;
 pub mod open_board;
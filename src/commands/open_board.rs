@@ -1,13 +1,15 @@
 mod static_files;
 
-use crate::board::{Board, BoardData, BoardIssueData};
+use crate::board::{Board, BoardBacklogData, BoardComment, BoardData, BoardFilter, BoardIssueData};
+use crate::commands::create_issues::{create_prepared_issues, prepare_issues, BulkIssueResult};
+use crate::commands::edit_issue::IssueChanges;
 use crate::commands::open_board::static_files::{StaticFile, StaticSource};
 use crate::config::Config;
 use crate::issue_code;
 use crate::issue_code::{parse_issue_markdown, prepare_api_body};
-use crate::jira_api::JiraApi;
-use crate::local_jira_cache::LocalJiraCache;
-use anyhow::{ensure, Context, Error, Result};
+use crate::jira_api::{JiraApi, JiraError, RankPosition};
+use crate::local_jira_cache::{CacheStats, LocalJiraCache};
+use anyhow::{anyhow, ensure, Context, Error, Result};
 use axum::extract::FromRef;
 use axum::extract::{Path, Query, State};
 use axum::http::{HeaderValue, Method, StatusCode};
@@ -15,8 +17,12 @@ use axum::response::{IntoResponse, Response};
 use axum::routing::{get, post};
 use axum::{Json, Router, Server};
 use directories::ProjectDirs;
+use futures::{future, stream, StreamExt};
 use itertools::Itertools;
-use serde::Deserialize;
+use serde::{Deserialize, Serialize};
+use serde_json::Map;
+use std::collections::BTreeMap;
+use std::mem;
 use std::net::IpAddr;
 use std::process::Command;
 use std::sync::Arc;
@@ -52,13 +58,23 @@ async fn get_favicon(source: State<StaticSource>) -> impl IntoResponse {
     StaticFile::Favicon.serve(source.0)
 }
 
-async fn get_api_board(State(board): State<Arc<Board>>) -> Result<Json<BoardData>, ApiError> {
+async fn get_api_board(
+    State(board): State<Arc<Board>>,
+    Query(filter): Query<BoardFilter>,
+) -> Result<Json<BoardData>, ApiError> {
     let start = Instant::now();
-    let data = board.load().await?;
+    let data = board.load(&filter).await?;
     tracing::info!("Got board data in {:.1}s", start.elapsed().as_secs_f64());
     Ok(Json(data))
 }
 
+async fn get_api_backlog(
+    State(board): State<Arc<Board>>,
+) -> Result<Json<BoardBacklogData>, ApiError> {
+    let data = board.backlog().await?;
+    Ok(Json(data))
+}
+
 async fn get_api_issue(
     State(board): State<Arc<Board>>,
     Path(key): Path<String>,
@@ -67,6 +83,157 @@ async fn get_api_issue(
     Ok(Json(data))
 }
 
+#[derive(Debug, Deserialize)]
+struct MoveIssueBody {
+    /// The name of the target column
+    column: String,
+}
+
+async fn post_move_issue(
+    Path(key): Path<String>,
+    State(api): State<Arc<JiraApi>>,
+    State(board): State<Arc<Board>>,
+    Json(body): Json<MoveIssueBody>,
+) -> Result<(), ApiError> {
+    if let Some(transition) = board.column_transition(key.clone(), &body.column).await? {
+        api.transition_issue(&key, &transition.id, &Map::new())
+            .await?;
+        board
+            .invalidate_issue(&key, Some(&transition.to.id))
+            .await?;
+    }
+
+    Ok(())
+}
+
+#[derive(Debug, Deserialize)]
+struct RankIssuesBody {
+    issues: Vec<String>,
+    /// The key of the issue to rank them before. Exclusive with `after`.
+    before: Option<String>,
+    after: Option<String>,
+}
+
+async fn post_rank_issues(
+    State(api): State<Arc<JiraApi>>,
+    State(cached_api): State<Arc<LocalJiraCache>>,
+    Json(body): Json<RankIssuesBody>,
+) -> Result<(), ApiError> {
+    let position = match (&body.before, &body.after) {
+        (Some(before), None) => RankPosition::Before(before),
+        (None, Some(after)) => RankPosition::After(after),
+        _ => return Err(anyhow!("Exactly one of before and after must be given").into()),
+    };
+
+    api.rank_issues(&body.issues, position).await?;
+    cached_api.invalidate_board_issues();
+
+    Ok(())
+}
+
+#[derive(Debug, Deserialize)]
+struct MoveIssuesBody {
+    issues: Vec<String>,
+    sprint: Option<u64>,
+    /// The name of the target column
+    column: Option<String>,
+}
+
+async fn post_move_issues(
+    State(config): State<Arc<Config>>,
+    State(api): State<Arc<JiraApi>>,
+    State(cached_api): State<Arc<LocalJiraCache>>,
+    State(board): State<Arc<Board>>,
+    Json(body): Json<MoveIssuesBody>,
+) -> Result<(), ApiError> {
+    if let Some(sprint_id) = body.sprint {
+        api.move_issues_to_sprint(sprint_id, &body.issues).await?;
+    }
+
+    let mut failures = vec![];
+    if let Some(column) = &body.column {
+        let moves = body
+            .issues
+            .iter()
+            .map(|key| async {
+                if let Some(transition) = board.column_transition(key.clone(), column).await? {
+                    api.transition_issue(key, &transition.id, &Map::new())
+                        .await?;
+                }
+                Ok::<_, Error>(())
+            })
+            .collect_vec();
+        let results: Vec<_> = stream::iter(moves)
+            .buffered(config.api_parallelism)
+            .collect()
+            .await;
+
+        for (key, result) in body.issues.iter().zip(results) {
+            if let Err(error) = result {
+                failures.push(format!("{}: {:#}", key, error));
+            }
+        }
+    }
+
+    // Some issues may have moved even if others failed
+    cached_api.invalidate_board_issues();
+    for key in &body.issues {
+        cached_api.invalidate_issue(key, &[]);
+    }
+
+    if !failures.is_empty() {
+        return Err(anyhow!(
+            "Failed to move {} issues: {}",
+            failures.len(),
+            failures.join("; ")
+        )
+        .into());
+    }
+
+    Ok(())
+}
+
+async fn get_api_comments(
+    State(board): State<Arc<Board>>,
+    Path(key): Path<String>,
+) -> Result<Json<Vec<BoardComment>>, ApiError> {
+    let comments = board.comments(key).await?;
+    Ok(Json(comments))
+}
+
+async fn post_comment(
+    Path(key): Path<String>,
+    State(api): State<Arc<JiraApi>>,
+    State(board): State<Arc<Board>>,
+    body: String,
+) -> Result<(), ApiError> {
+    api.add_comment(&key, &body).await?;
+    board.invalidate_issue(&key, None).await?;
+    Ok(())
+}
+
+async fn post_edit_comment(
+    Path((key, id)): Path<(String, String)>,
+    State(api): State<Arc<JiraApi>>,
+    State(board): State<Arc<Board>>,
+    body: String,
+) -> Result<(), ApiError> {
+    api.edit_comment(&key, &id, &body).await?;
+    board.invalidate_issue(&key, None).await?;
+    Ok(())
+}
+
+async fn get_api_debug_cache(State(cached_api): State<Arc<LocalJiraCache>>) -> Json<CacheStats> {
+    Json(cached_api.stats())
+}
+
+#[derive(Debug, Deserialize)]
+struct GetEditIssueCodeQuery {
+    /// Whether to quote the comments in the code, so that a new one can be added
+    #[serde(default)]
+    comments: bool,
+}
+
 #[derive(Debug, Deserialize)]
 struct GetNewIssueCodeQuery {
     status_ids: String,
@@ -89,9 +256,24 @@ async fn get_edit_issue_code(
     Path(key): Path<String>,
     State(config): State<Arc<Config>>,
     State(api): State<Arc<LocalJiraCache>>,
+    State(board): State<Arc<Board>>,
+    Query(query): Query<GetEditIssueCodeQuery>,
 ) -> Result<String, ApiError> {
+    let comments = if query.comments {
+        Some(api.comments(key.clone()).await?)
+    } else {
+        None
+    };
+    let transitions = board.transitions(key.clone()).await?;
+    let sprints = board.sprints().await?;
     let issue = api.issue(key).await?;
-    let code = issue_code::edit_issue(&config, issue.fields)?;
+    let code = issue_code::edit_issue(
+        &config,
+        issue.fields,
+        &transitions,
+        &sprints,
+        comments.as_deref(),
+    )?;
     Ok(code)
 }
 
@@ -99,46 +281,98 @@ async fn post_new_issue(
     State(config): State<Arc<Config>>,
     State(api): State<Arc<JiraApi>>,
     State(cached_api): State<Arc<LocalJiraCache>>,
+    State(board): State<Arc<Board>>,
     code: String,
 ) -> Result<(), ApiError> {
-    let info = parse_issue_markdown(&code).context("Failed to parse Markdown")?;
+    let mut info = parse_issue_markdown(&code).context("Failed to parse Markdown")?;
+    let new_comment = info.new_comment.take();
+    let links = mem::take(&mut info.links);
+    let subtasks = mem::take(&mut info.subtasks);
+    let sprint_id = match info.sprint.take() {
+        None => None,
+        Some(sprint) => Some(board.sprint_id(&sprint).await?),
+    };
     let body = prepare_api_body(&config, info).context("Failed to prepare Jira API call")?;
 
     tracing::info!("Will request Jira API");
-    let key = api.create_issue(&body).await?;
+    let key = api
+        .create_issue(&body)
+        .await
+        .map_err(|error| issue_code::with_kaiju_field_names(&config, error))?;
     tracing::info!("Created issue: {}/browse/{}", config.api_host, key);
 
-    cached_api.clear();
+    // The issue exists now, so every other step is attempted and the board is refreshed anyway,
+    // instead of inviting the user to create it again
+    let mut errors = vec![];
+    if let Some(new_comment) = new_comment {
+        errors.extend(api.add_comment(&key, &new_comment).await.err());
+    }
+    if let Some(sprint_id) = sprint_id {
+        errors.extend(
+            api.move_issues_to_sprint(sprint_id, &[key.clone()])
+                .await
+                .err(),
+        );
+    }
+    errors.extend(
+        api.link_issues_and_create_subtasks(&key, &links, &subtasks)
+            .await
+            .err(),
+    );
 
+    cached_api.invalidate_board_issues();
+
+    if !errors.is_empty() {
+        return Err(anyhow!(
+            "Created issue {}, but failed to complete it: {}",
+            key,
+            errors
+                .iter()
+                .map(|error| format!("{:#}", error))
+                .format("; ")
+        )
+        .into());
+    }
     Ok(())
 }
 
+async fn post_new_issues(
+    State(config): State<Arc<Config>>,
+    State(api): State<Arc<JiraApi>>,
+    State(cached_api): State<Arc<LocalJiraCache>>,
+    code: String,
+) -> Result<Json<Vec<BulkIssueResult>>, ApiError> {
+    let issues = prepare_issues(&config, &code)?;
+
+    tracing::info!("Will request Jira API");
+    let results = create_prepared_issues(&api, &config, issues).await?;
+
+    cached_api.invalidate_board_issues();
+
+    Ok(Json(results))
+}
+
 async fn post_edit_issue(
     Path(key): Path<String>,
     State(config): State<Arc<Config>>,
     State(api): State<Arc<JiraApi>>,
     State(cached_api): State<Arc<LocalJiraCache>>,
+    State(board): State<Arc<Board>>,
     code: String,
 ) -> Result<(), ApiError> {
-    let info = parse_issue_markdown(&code).context("Failed to parse Markdown")?;
+    let transitions = board.transitions(key.clone()).await?;
+    let sprints = board.sprints().await?;
+    let changes = IssueChanges::parse(&config, &code, &transitions, &sprints)?;
 
-    if let Some(transition_name) = &info.transition {
-        let transition = config
-            .transitions
-            .iter()
-            .find(|transition| &transition.name == transition_name)
-            .with_context(|| format!("Transition {} is not known", transition_name))?;
+    let result = changes.apply(&config, &api, &key).await;
 
-        api.transition_issue(&key, &transition.id).await?;
+    // The issue can now appear in the columns of another sprint, or have new subtasks
+    if changes.sprint_id.is_some() || !changes.subtasks.is_empty() {
+        cached_api.invalidate_board_issues();
     }
+    board.invalidate_issue(&key, changes.to_status_id()).await?;
 
-    let body = prepare_api_body(&config, info).context("Failed to prepare Jira API call")?;
-    tracing::info!("Will request Jira API");
-    api.edit_issue(&key, &body).await?;
-
-    cached_api.clear();
-
-    Ok(())
+    Ok(result?)
 }
 
 pub async fn open_board(
@@ -158,9 +392,15 @@ pub async fn open_board(
     let cached_api = Arc::new(LocalJiraCache::new(
         api.clone(),
         config.api_parallelism,
+        config
+            .api_retry
+            .as_ref()
+            .map_or(false, |retry| retry.adaptive_parallelism),
         config.cache.clone(),
+        project_dirs,
     ));
-    let board = Arc::new(Board::open(&config, cached_api.clone(), board_name).await?);
+    cached_api.start_background_tasks();
+    let board = Arc::new(Board::open(config.clone(), cached_api.clone(), board_name).await?);
 
     let server_port = config.server_port;
     let ip: IpAddr = config.server_ip.parse()?;
@@ -174,14 +414,25 @@ pub async fn open_board(
         .route("/index.css", get(get_css))
         .route("/favicon.png", get(get_favicon))
         .route("/api/board", get(get_api_board))
+        .route("/api/board/backlog", get(get_api_backlog))
         .route("/api/issue/:key", get(get_api_issue))
         .route("/api/new-issue-code", get(get_new_issue_code))
         .route("/api/edit-issue-code/:key", get(get_edit_issue_code))
         .route("/api/issue", post(post_new_issue))
         .route("/api/issue/:key", post(post_edit_issue))
+        .route("/api/issue/:key/move", post(post_move_issue))
+        .route(
+            "/api/issue/:key/comments",
+            get(get_api_comments).post(post_comment),
+        )
+        .route("/api/issue/:key/comments/:id", post(post_edit_comment))
+        .route("/api/issues/bulk", post(post_new_issues))
+        .route("/api/issues/rank", post(post_rank_issues))
+        .route("/api/issues/move", post(post_move_issues))
+        .route("/api/debug/cache", get(get_api_debug_cache))
         .with_state(ApiState {
             api,
-            cached_api,
+            cached_api: cached_api.clone(),
             board,
             static_source,
             config,
@@ -196,7 +447,15 @@ pub async fn open_board(
                 }))
                 .allow_methods([Method::GET]),
         );
-    let server = Server::bind(&(ip, server_port).into()).serve(app.into_make_service());
+    let server = Server::bind(&(ip, server_port).into())
+        .serve(app.into_make_service())
+        .with_graceful_shutdown(async {
+            if let Err(error) = tokio::signal::ctrl_c().await {
+                tracing::warn!("Failed to listen for Ctrl-C: {}", error);
+                future::pending::<()>().await;
+            }
+            tracing::info!("Shutting down");
+        });
 
     if !no_browser {
         task::spawn_blocking(move || {
@@ -210,6 +469,9 @@ pub async fn open_board(
 
     server.await?;
 
+    // Do not lose what was loaded since the last periodic save
+    cached_api.persist().await?;
+
     Ok(())
 }
 
@@ -226,10 +488,37 @@ fn open_browser(url: &str) -> Result<()> {
     Ok(())
 }
 
+#[derive(Debug, Serialize)]
+struct ApiErrorBody {
+    message: String,
+    jira_messages: Vec<String>,
+    field_errors: BTreeMap<String, String>,
+}
+
 impl IntoResponse for ApiError {
     fn into_response(self) -> Response {
         tracing::warn!("Will answer endpoint with error: {:?}", self.0);
-        (StatusCode::INTERNAL_SERVER_ERROR, format!("{:#}", self.0)).into_response()
+
+        let jira_error = JiraError::find(&self.0);
+        let status = match jira_error {
+            None => StatusCode::INTERNAL_SERVER_ERROR,
+            Some(jira_error) if jira_error.status.is_client_error() => {
+                StatusCode::from_u16(jira_error.status.as_u16()).unwrap_or(StatusCode::BAD_REQUEST)
+            }
+            Some(_) => StatusCode::BAD_GATEWAY,
+        };
+
+        let body = ApiErrorBody {
+            message: format!("{:#}", self.0),
+            jira_messages: jira_error
+                .map(|jira_error| jira_error.messages.clone())
+                .unwrap_or_default(),
+            field_errors: jira_error
+                .map(|jira_error| jira_error.field_errors.clone())
+                .unwrap_or_default(),
+        };
+
+        (status, Json(body)).into_response()
     }
 }
 
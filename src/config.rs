@@ -5,13 +5,18 @@ use std::path::PathBuf;
 
 use anyhow::{Context, Result};
 use directories::ProjectDirs;
-use serde::Deserialize;
+use serde::{Deserialize, Serialize};
 
 #[derive(Debug, Clone, Deserialize)]
 pub struct Config {
     pub api_host: String,
     pub api_parallelism: usize,
     pub api_timeout_seconds: u64,
+    pub api_retry: Option<RetryConfig>,
+    #[serde(default)]
+    pub development_info_providers: DevelopmentInfoProviders,
+    #[serde(default)]
+    pub forges: Vec<ForgeConfig>,
     pub email: String,
     pub token: String,
     pub server_port: u16,
@@ -19,10 +24,40 @@ pub struct Config {
     pub issue_fields: Vec<IssueFieldConfig>,
     pub value_bag: BTreeMap<String, BTreeMap<String, String>>,
     pub transitions: Vec<TransitionConfig>,
+    pub subtask_issue_type: Option<String>,
     pub board: BTreeMap<String, BoardLocalConfig>,
     pub cache: CacheConfig,
 }
 
+#[derive(Debug, Clone, Deserialize)]
+pub struct RetryConfig {
+    pub max_attempts: u32,
+    pub initial_backoff_ms: u64,
+    pub max_backoff_ms: u64,
+    pub adaptive_parallelism: bool,
+}
+
+#[derive(Debug, Clone, Hash, Eq, PartialEq, Deserialize, Serialize)]
+pub struct DevelopmentInfoProviders {
+    pub applications: Vec<String>,
+    pub data_types: Vec<String>,
+}
+
+#[derive(Debug, Clone, Eq, PartialEq, Deserialize)]
+pub struct ForgeConfig {
+    pub kind: ForgeKind,
+    pub web_host: String,
+    pub api_host: String,
+    pub token: String,
+}
+
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ForgeKind {
+    GitHub,
+    GitLab,
+}
+
 #[derive(Debug, Clone, Deserialize)]
 pub struct IssueFieldConfig {
     pub name: String,
@@ -56,6 +91,43 @@ pub struct BoardLocalConfig {
     pub epic_short_name: String,
     pub epic_color: Option<String>,
     pub flag: Option<String>,
+    pub max_cards_per_column: Option<usize>,
+    #[serde(default)]
+    pub development_info: DevelopmentInfoMode,
+    pub development_info_providers: Option<DevelopmentInfoProviders>,
+    pub swimlanes: Option<SwimlanesConfig>,
+    #[serde(default)]
+    pub quick_filters: Vec<QuickFilterConfig>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct QuickFilterConfig {
+    pub name: String,
+    /// A JQL condition, combined with the ones of the columns
+    pub jql: String,
+}
+
+/// How to group the issues of a board in horizontal lanes
+#[derive(Debug, Clone, Eq, PartialEq, Deserialize)]
+#[serde(tag = "by", rename_all = "lowercase")]
+pub enum SwimlanesConfig {
+    Epic,
+    Avatar,
+    /// One of the declared issue fields, by name
+    Field {
+        field: String,
+    },
+}
+
+#[derive(Debug, Clone, Copy, Default, Eq, PartialEq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum DevelopmentInfoMode {
+    /// Load it for all cards in the board
+    #[default]
+    Eager,
+    /// Load it only when a card is opened
+    Lazy,
+    Disabled,
 }
 
 #[derive(Debug, Clone, Deserialize)]
@@ -65,6 +137,31 @@ pub struct CacheConfig {
     pub ttl_issue_seconds: u64,
     pub ttl_epic_seconds: u64,
     pub ttl_development_info_seconds: u64,
+    pub ttl_merge_request_status_seconds: Option<u64>,
+    pub ttl_transitions_seconds: Option<u64>,
+    pub ttl_sprints_seconds: Option<u64>,
+    pub ttl_retryable_error_seconds: Option<u64>,
+    pub ttl_permanent_error_seconds: Option<u64>,
+    pub stale_while_revalidate_seconds: Option<u64>,
+    pub max_entries: Option<usize>,
+    pub max_size_bytes: Option<usize>,
+    pub sweep_interval_seconds: Option<u64>,
+    pub persistence: Option<CachePersistenceConfig>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct CachePersistenceConfig {
+    pub save_interval_seconds: u64,
+    pub max_size_bytes: usize,
+}
+
+impl Default for DevelopmentInfoProviders {
+    fn default() -> Self {
+        DevelopmentInfoProviders {
+            applications: vec!["githube".to_owned()],
+            data_types: vec!["pullrequest".to_owned()],
+        }
+    }
 }
 
 const DEFAULT_CONFIG: &str = include_str!("../resources/default_config.toml");
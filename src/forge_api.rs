@@ -0,0 +1,530 @@
+use crate::config::{Config, ForgeConfig, ForgeKind};
+use anyhow::{Context, Result};
+use itertools::Itertools;
+use reqwest::{Client, RequestBuilder};
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use std::time::Duration;
+
+/// How many items to ask for in each page of GitHub lists, which is the most it allows
+const GITHUB_PAGE_SIZE: usize = 100;
+
+/// Talks directly to the Git forges (GitHub or GitLab) hosting the merge requests, which are usually
+/// more up-to-date than the development info in Jira
+#[derive(Debug)]
+pub struct ForgeApi {
+    client: Client,
+    forges: Vec<ForgeConfig>,
+}
+
+/// The live status of a merge request, as reported by its forge
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MergeRequestStatus {
+    /// Like "open", "merged" or "closed"
+    pub state: String,
+    pub draft: bool,
+    pub approvals: usize,
+    pub changes_requested: bool,
+    /// The combined state of the CI checks, like "success", "pending" or "failure"
+    pub checks: Option<String>,
+    /// Whether the merge request can be merged right now, if known
+    pub mergeable: Option<bool>,
+}
+
+/// A merge request, identified in a forge
+#[derive(Debug, Clone, Eq, PartialEq)]
+struct MergeRequestRef<'a> {
+    forge: &'a ForgeConfig,
+    /// The path of the repository, like "owner/repo" or "group/subgroup/project"
+    repository: String,
+    number: u64,
+}
+
+impl ForgeApi {
+    pub fn new(config: &Config) -> Self {
+        ForgeApi {
+            client: Client::builder()
+                .timeout(Duration::from_secs(config.api_timeout_seconds))
+                .user_agent(concat!("kaiju/", env!("CARGO_PKG_VERSION")))
+                .build()
+                .unwrap(),
+            forges: config.forges.clone(),
+        }
+    }
+
+    /// Whether the merge request with this URL is hosted in one of the configured forges
+    pub fn knows(&self, url: &str) -> bool {
+        self.parse_url(url).is_some()
+    }
+
+    pub async fn merge_request_status(&self, url: &str) -> Result<MergeRequestStatus> {
+        let merge_request = self
+            .parse_url(url)
+            .with_context(|| format!("No forge is configured for {}", url))?;
+
+        tracing::debug!("Load merge request status for {}", url);
+        match merge_request.forge.kind {
+            ForgeKind::GitHub => self.github_status(&merge_request).await,
+            ForgeKind::GitLab => self.gitlab_status(&merge_request).await,
+        }
+    }
+
+    fn parse_url(&self, url: &str) -> Option<MergeRequestRef> {
+        self.forges.iter().find_map(|forge| {
+            let path = url
+                .strip_prefix(forge.web_host.trim_end_matches('/'))?
+                .trim_matches('/');
+
+            let separator = match forge.kind {
+                ForgeKind::GitHub => "/pull/",
+                ForgeKind::GitLab => "/-/merge_requests/",
+            };
+            let (repository, rest) = path.split_once(separator)?;
+            let number = rest.split('/').next()?.parse().ok()?;
+
+            Some(MergeRequestRef {
+                forge,
+                repository: repository.to_owned(),
+                number,
+            })
+        })
+    }
+
+    async fn github_status(
+        &self,
+        merge_request: &MergeRequestRef<'_>,
+    ) -> Result<MergeRequestStatus> {
+        #[derive(Debug, Deserialize)]
+        struct Pull {
+            state: String,
+            merged: bool,
+            draft: bool,
+            mergeable: Option<bool>,
+            head: Head,
+        }
+
+        #[derive(Debug, Deserialize)]
+        struct Head {
+            sha: String,
+        }
+
+        #[derive(Debug, Deserialize)]
+        struct Review {
+            user: User,
+            state: String,
+        }
+
+        #[derive(Debug, Deserialize)]
+        struct User {
+            login: String,
+        }
+
+        #[derive(Debug, Deserialize)]
+        struct CombinedStatus {
+            state: String,
+            total_count: usize,
+        }
+
+        #[derive(Debug, Deserialize)]
+        struct CheckRuns {
+            check_runs: Vec<CheckRun>,
+        }
+
+        #[derive(Debug, Deserialize)]
+        struct CheckRun {
+            status: String,
+            conclusion: Option<String>,
+        }
+
+        let repository_url = format!(
+            "{}/repos/{}",
+            merge_request.forge.api_host, merge_request.repository
+        );
+        let pull_url = format!("{}/pulls/{}", repository_url, merge_request.number);
+
+        let pull: Pull = self
+            .request(merge_request.forge, self.client.get(&pull_url))
+            .await?;
+        let reviews: Vec<Review> = self
+            .github_pages(
+                merge_request.forge,
+                &format!("{}/reviews", pull_url),
+                |page| page,
+            )
+            .await?;
+        let commit_url = format!("{}/commits/{}", repository_url, pull.head.sha);
+        let check_runs = self
+            .github_pages(
+                merge_request.forge,
+                &format!("{}/check-runs", commit_url),
+                |page: CheckRuns| page.check_runs,
+            )
+            .await?;
+        let status: CombinedStatus = self
+            .request(
+                merge_request.forge,
+                self.client.get(format!("{}/status", commit_url)),
+            )
+            .await?;
+
+        // Commit statuses and check runs are two separate kinds of CI checks
+        let mut check_states = check_runs
+            .iter()
+            .map(|check_run| match check_run.conclusion.as_deref() {
+                _ if check_run.status != "completed" => "pending",
+                Some("success" | "neutral" | "skipped") => "success",
+                _ => "failure",
+            })
+            .collect_vec();
+        if status.total_count > 0 {
+            check_states.push(match status.state.as_str() {
+                "error" => "failure",
+                state => state,
+            });
+        }
+        let checks = ["failure", "pending", "success"]
+            .into_iter()
+            .find(|state| check_states.contains(state));
+
+        // Only the last review of each user counts
+        let mut last_reviews = Vec::<&Review>::new();
+        for review in reviews.iter().rev() {
+            if review.state != "COMMENTED"
+                && !last_reviews
+                    .iter()
+                    .any(|last_review| last_review.user.login == review.user.login)
+            {
+                last_reviews.push(review);
+            }
+        }
+
+        Ok(MergeRequestStatus {
+            state: if pull.merged {
+                "merged".to_owned()
+            } else {
+                pull.state
+            },
+            draft: pull.draft,
+            approvals: last_reviews
+                .iter()
+                .filter(|review| review.state == "APPROVED")
+                .count(),
+            changes_requested: last_reviews
+                .iter()
+                .any(|review| review.state == "CHANGES_REQUESTED"),
+            checks: checks.map(str::to_owned),
+            mergeable: pull.mergeable,
+        })
+    }
+
+    async fn gitlab_status(
+        &self,
+        merge_request: &MergeRequestRef<'_>,
+    ) -> Result<MergeRequestStatus> {
+        #[derive(Debug, Deserialize)]
+        struct MergeRequest {
+            state: String,
+            #[serde(default)]
+            draft: bool,
+            detailed_merge_status: Option<String>,
+            head_pipeline: Option<Pipeline>,
+        }
+
+        #[derive(Debug, Deserialize)]
+        struct Pipeline {
+            status: String,
+        }
+
+        #[derive(Debug, Deserialize)]
+        struct Approvals {
+            approved_by: Vec<serde_json::Value>,
+        }
+
+        let merge_request_url = format!(
+            "{}/api/v4/projects/{}/merge_requests/{}",
+            merge_request.forge.api_host,
+            merge_request.repository.replace('/', "%2F"),
+            merge_request.number
+        );
+
+        let data: MergeRequest = self
+            .request(merge_request.forge, self.client.get(&merge_request_url))
+            .await?;
+        let approvals: Approvals = self
+            .request(
+                merge_request.forge,
+                self.client.get(format!("{}/approvals", merge_request_url)),
+            )
+            .await?;
+
+        let state = match data.state.as_str() {
+            "opened" => "open".to_owned(),
+            _ => data.state,
+        };
+
+        Ok(MergeRequestStatus {
+            state,
+            draft: data.draft,
+            approvals: approvals.approved_by.len(),
+            changes_requested: data.detailed_merge_status.as_deref() == Some("requested_changes"),
+            checks: data.head_pipeline.map(|pipeline| {
+                match pipeline.status.as_str() {
+                    "success" | "skipped" => "success",
+                    "failed" | "canceled" => "failure",
+                    _ => "pending",
+                }
+                .to_owned()
+            }),
+            mergeable: data
+                .detailed_merge_status
+                .map(|status| status == "mergeable"),
+        })
+    }
+
+    /// Load all items of a paginated GitHub list, extracting them from each page with `items`
+    async fn github_pages<P: DeserializeOwned, T>(
+        &self,
+        forge: &ForgeConfig,
+        url: &str,
+        items: fn(P) -> Vec<T>,
+    ) -> Result<Vec<T>> {
+        let mut all_items = vec![];
+        let mut page = 1;
+        loop {
+            let page_items = items(
+                self.request(
+                    forge,
+                    self.client
+                        .get(url)
+                        .query(&[("per_page", GITHUB_PAGE_SIZE), ("page", page)]),
+                )
+                .await?,
+            );
+            let is_last_page = page_items.len() < GITHUB_PAGE_SIZE;
+            all_items.extend(page_items);
+            if is_last_page {
+                return Ok(all_items);
+            }
+            page += 1;
+        }
+    }
+
+    async fn request<T: DeserializeOwned>(
+        &self,
+        forge: &ForgeConfig,
+        request: RequestBuilder,
+    ) -> Result<T> {
+        let request = match forge.kind {
+            ForgeKind::GitHub => request.bearer_auth(&forge.token),
+            ForgeKind::GitLab => request.header("PRIVATE-TOKEN", &forge.token),
+        };
+
+        let response = request.send().await?.error_for_status()?.json().await?;
+
+        Ok(response)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::jira_api::FailureKind;
+    use axum::extract::{Path, Query};
+    use axum::http::StatusCode;
+    use axum::routing::get;
+    use axum::{Json, Router, Server};
+    use serde_json::{json, Value};
+    use std::collections::HashMap;
+    use std::net::SocketAddr;
+
+    fn forge_api(kind: ForgeKind, api_host: String) -> ForgeApi {
+        ForgeApi {
+            client: Client::new(),
+            forges: vec![ForgeConfig {
+                kind,
+                web_host: "https://forge.example.com".to_owned(),
+                api_host,
+                token: "secret".to_owned(),
+            }],
+        }
+    }
+
+    async fn serve(app: Router) -> String {
+        let server =
+            Server::bind(&SocketAddr::from(([127, 0, 0, 1], 0))).serve(app.into_make_service());
+        let address = server.local_addr();
+        tokio::spawn(server);
+        format!("http://{}", address)
+    }
+
+    #[test]
+    fn parse_urls() {
+        let github = forge_api(ForgeKind::GitHub, String::new());
+        let merge_request = github
+            .parse_url("https://forge.example.com/owner/repo/pull/17/files")
+            .unwrap();
+        assert_eq!(merge_request.repository, "owner/repo");
+        assert_eq!(merge_request.number, 17);
+        assert!(!github.knows("https://other.example.com/owner/repo/pull/17"));
+
+        let gitlab = forge_api(ForgeKind::GitLab, String::new());
+        let merge_request = gitlab
+            .parse_url("https://forge.example.com/group/sub/project/-/merge_requests/3")
+            .unwrap();
+        assert_eq!(merge_request.repository, "group/sub/project");
+        assert_eq!(merge_request.number, 3);
+    }
+
+    #[tokio::test]
+    async fn github_status() {
+        let app = Router::new()
+            .route(
+                "/repos/owner/repo/pulls/17",
+                get(|| async {
+                    Json(json!({
+                        "state": "open",
+                        "merged": false,
+                        "draft": false,
+                        "mergeable": true,
+                        "head": {"sha": "abc"},
+                    }))
+                }),
+            )
+            .route(
+                "/repos/owner/repo/pulls/17/reviews",
+                get(|Query(query): Query<HashMap<String, String>>| async move {
+                    // A full first page, so the second one is requested too
+                    let first_page = vec![
+                        json!({"user": {"login": "alice"}, "state": "CHANGES_REQUESTED"});
+                        100
+                    ];
+                    match query["page"].as_str() {
+                        "1" => Json(json!(first_page)),
+                        _ => Json(json!([
+                            {"user": {"login": "bob"}, "state": "APPROVED"},
+                            {"user": {"login": "alice"}, "state": "APPROVED"},
+                            {"user": {"login": "carol"}, "state": "COMMENTED"},
+                        ])),
+                    }
+                }),
+            )
+            .route(
+                "/repos/owner/repo/commits/abc/check-runs",
+                get(|| async {
+                    Json(json!({"total_count": 2, "check_runs": [
+                        {"status": "completed", "conclusion": "success"},
+                        {"status": "completed", "conclusion": "skipped"},
+                    ]}))
+                }),
+            )
+            .route(
+                "/repos/owner/repo/commits/:sha/status",
+                get(|Path(sha): Path<String>| async move {
+                    let state = if sha == "abc" { "success" } else { "failure" };
+                    Json(json!({"state": state, "total_count": 2}))
+                }),
+            );
+        let api = forge_api(ForgeKind::GitHub, serve(app).await);
+
+        let status = api
+            .merge_request_status("https://forge.example.com/owner/repo/pull/17")
+            .await
+            .unwrap();
+
+        assert_eq!(status.state, "open");
+        assert_eq!(status.approvals, 2);
+        assert!(!status.changes_requested);
+        assert_eq!(status.checks.as_deref(), Some("success"));
+        assert_eq!(status.mergeable, Some(true));
+    }
+
+    #[tokio::test]
+    async fn github_check_runs_and_server_errors() {
+        let app = Router::new()
+            .route(
+                "/repos/owner/repo/pulls/:number",
+                get(|Path(number): Path<u64>| async move {
+                    if number == 18 {
+                        return Err(StatusCode::BAD_GATEWAY);
+                    }
+                    Ok(Json(json!({
+                        "state": "open",
+                        "merged": false,
+                        "draft": false,
+                        "mergeable": null,
+                        "head": {"sha": "abc"},
+                    })))
+                }),
+            )
+            .route(
+                "/repos/owner/repo/pulls/17/reviews",
+                get(|| async { Json(json!([])) }),
+            )
+            .route(
+                "/repos/owner/repo/commits/abc/check-runs",
+                get(|| async {
+                    Json(json!({"total_count": 2, "check_runs": [
+                        {"status": "completed", "conclusion": "success"},
+                        {"status": "in_progress", "conclusion": null},
+                    ]}))
+                }),
+            )
+            .route(
+                "/repos/owner/repo/commits/abc/status",
+                get(|| async { Json(json!({"state": "pending", "total_count": 0})) }),
+            );
+        let api = forge_api(ForgeKind::GitHub, serve(app).await);
+
+        let status = api
+            .merge_request_status("https://forge.example.com/owner/repo/pull/17")
+            .await
+            .unwrap();
+        assert_eq!(status.checks.as_deref(), Some("pending"));
+
+        let error = api
+            .merge_request_status("https://forge.example.com/owner/repo/pull/18")
+            .await
+            .unwrap_err();
+        assert_eq!(FailureKind::of(&error), FailureKind::Retryable);
+    }
+
+    #[tokio::test]
+    async fn gitlab_status() {
+        let app = Router::new()
+            .route(
+                "/api/v4/projects/:project/merge_requests/:number",
+                get(|Path((project, number)): Path<(String, u64)>| async move {
+                    assert_eq!(project, "group/project");
+                    let pipeline_status = if number == 3 { "running" } else { "canceled" };
+                    Json(json!({
+                        "state": "opened",
+                        "draft": true,
+                        "detailed_merge_status": "draft_status",
+                        "head_pipeline": {"status": pipeline_status},
+                    }))
+                }),
+            )
+            .route(
+                "/api/v4/projects/:project/merge_requests/:number/approvals",
+                get(|| async { Json(json!({ "approved_by": [Value::Null] })) }),
+            );
+        let api = forge_api(ForgeKind::GitLab, serve(app).await);
+
+        let status = api
+            .merge_request_status("https://forge.example.com/group/project/-/merge_requests/3")
+            .await
+            .unwrap();
+
+        assert_eq!(status.state, "open");
+        assert!(status.draft);
+        assert_eq!(status.approvals, 1);
+        assert_eq!(status.checks.as_deref(), Some("pending"));
+        assert_eq!(status.mergeable, Some(false));
+
+        let status = api
+            .merge_request_status("https://forge.example.com/group/project/-/merge_requests/4")
+            .await
+            .unwrap();
+        assert_eq!(status.checks.as_deref(), Some("failure"));
+    }
+}
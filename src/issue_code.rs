@@ -1,14 +1,35 @@
 use crate::config::{Config, IssueFieldConfig, IssueFieldValuesConfig};
-use anyhow::{ensure, Context, Result};
+use crate::jira_api::{Comment, JiraError, NewIssueLink, Sprint, Transition, WorkLog};
+use anyhow::{bail, ensure, Context, Error, Result};
 use itertools::Itertools;
 use serde_json::{json, Map, Value};
 use std::collections::{BTreeMap, BTreeSet};
 use std::fmt::Write;
+use std::mem;
 
 const COMMENT_PREFIX: &str = "<!--";
 const SEPARATOR: &str = ", ";
 const COMMENT_SUFFIX: &str = "-->";
 const TRANSITION_COMMAND: &str = "Transition";
+const LOG_COMMAND: &str = "Log";
+const SPRINT_COMMAND: &str = "Sprint";
+/// The commands that link the issue to others: the command, the name of the Jira link type and
+/// whether the other issues are on the outward side of the link
+const LINK_COMMANDS: &[(&str, &str, bool)] = &[
+    ("Blocks", "Blocks", true),
+    ("Blocked by", "Blocks", false),
+    ("Relates to", "Relates", true),
+];
+const COMMENTS_HEADER: &str = "# Comments";
+/// The lines around the existing comments, which only Kaiju writes. Quoted lines between them are
+/// the existing comments and everything after the second one is the new comment
+const COMMENTS_MARKER: &str = "<!-- kaiju:comments -->";
+const NEW_COMMENT_MARKER: &str = "<!-- kaiju:new-comment Write a new comment below -->";
+const SUBTASKS_HEADER: &str = "## Subtasks";
+/// The line separating the issues in a document with many of them. An HTML comment renders as
+/// nothing, so it can't be mistaken for Markdown in a description
+const ISSUE_SEPARATOR: &str = "<!-- kaiju:next-issue -->";
+const QUOTE_PREFIX: &str = ">";
 
 /// Return the Kaiju markdown code to create a new issue
 pub fn new_issue(config: &Config, filter_status_ids: Option<&[String]>) -> Result<String> {
@@ -53,9 +74,44 @@ pub fn new_issue(config: &Config, filter_status_ids: Option<&[String]>) -> Resul
     Ok(contents)
 }
 
+/// Return the Kaiju markdown code to create many issues at once, with two of them to start with
+pub fn new_issues(config: &Config) -> Result<String> {
+    let issue = new_issue(config, None)?;
+    Ok(format!("{}\n{}\n\n{}", issue, ISSUE_SEPARATOR, issue))
+}
+
+/// Split a document with many issues, separated by lines with only `ISSUE_SEPARATOR`. Empty issues
+/// are ignored
+pub fn split_issues_markdown(source: &str) -> Vec<String> {
+    let mut issues = vec![];
+    let mut current = String::new();
+    for line in source.lines() {
+        if line.trim() == ISSUE_SEPARATOR {
+            issues.push(mem::take(&mut current));
+        } else {
+            current.push_str(line);
+            current.push('\n');
+        }
+    }
+    issues.push(current);
+
+    issues
+        .into_iter()
+        .filter(|issue| !issue.trim().is_empty())
+        .collect()
+}
+
 /// Return the Kaiju markdown code to edit the given issue (as returned by Jira's API).
-/// Only the fields declared in the config will be considered.
-pub fn edit_issue(config: &Config, fields: Value) -> Result<String> {
+/// Only the fields declared in the config will be considered. The given transitions are the ones
+/// available from the current status and the sprints are the ones the issue can be moved to. If
+/// comments are given, they are quoted in a final section, where a new comment can be written.
+pub fn edit_issue(
+    config: &Config,
+    fields: Value,
+    transitions: &[Transition],
+    sprints: &[Sprint],
+    comments: Option<&[Comment]>,
+) -> Result<String> {
     let mut contents = String::new();
 
     let summary = fields
@@ -79,15 +135,57 @@ pub fn edit_issue(config: &Config, fields: Value) -> Result<String> {
     writeln!(contents, "# Kaiju")?;
     writeln!(contents)?;
 
-    let transitions = config
-        .transitions
-        .iter()
-        .map(|transition| &transition.name)
-        .collect_vec();
     write_kaiju_values(
         &mut contents,
         TRANSITION_COMMAND,
-        transitions.into_iter(),
+        transitions.iter().map(|transition| &transition.name),
+        None.into_iter(),
+    )?;
+
+    // The fields that some transition screens require, like the resolution, unless they are
+    // declared in the config
+    let mut screen_fields = BTreeMap::new();
+    for transition in transitions {
+        for (id, field) in &transition.fields {
+            if field.required
+                && !field.allowed_values.is_empty()
+                && kaiju_field_name(config, id).is_none()
+            {
+                screen_fields.entry(&field.name).or_insert(field);
+            }
+        }
+    }
+    for (name, field) in screen_fields {
+        write_kaiju_values(
+            &mut contents,
+            name,
+            field
+                .allowed_values
+                .iter()
+                .filter_map(|value| value.label()),
+            None.into_iter(),
+        )?;
+    }
+
+    writeln!(
+        contents,
+        "{}{}: 1h30m, what was done{}",
+        COMMENT_PREFIX, LOG_COMMAND, COMMENT_SUFFIX
+    )?;
+    writeln!(
+        contents,
+        "{}{}{}",
+        COMMENT_PREFIX,
+        LINK_COMMANDS
+            .iter()
+            .map(|(command, _, _)| format!("{}: KEY-1", command))
+            .format(SEPARATOR),
+        COMMENT_SUFFIX
+    )?;
+    write_kaiju_values(
+        &mut contents,
+        SPRINT_COMMAND,
+        sprints.iter().map(|sprint| &sprint.name),
         None.into_iter(),
     )?;
 
@@ -133,9 +231,264 @@ pub fn edit_issue(config: &Config, fields: Value) -> Result<String> {
         }
     }
 
+    if let Some(comments) = comments {
+        write_comments(&mut contents, comments)?;
+    }
+
     Ok(contents)
 }
 
+/// Return the name used in the Kaiju code for the field with the given name in Jira's API, if it is
+/// declared in the config
+pub fn kaiju_field_name<'a>(config: &'a Config, jira_field: &str) -> Option<&'a str> {
+    config
+        .issue_fields
+        .iter()
+        .find(|issue_field| jira_field_name(issue_field) == Some(jira_field))
+        .map(|issue_field| issue_field.name.as_str())
+}
+
+/// Translate the field names in the errors answered by Jira to the ones used in the Kaiju code, so
+/// that the user knows what to fix
+pub fn with_kaiju_field_names(config: &Config, mut error: Error) -> Error {
+    if let Some(jira_error) = error.downcast_mut::<JiraError>() {
+        jira_error.field_errors = mem::take(&mut jira_error.field_errors)
+            .into_iter()
+            .map(|(field, message)| {
+                let name = kaiju_field_name(config, &field)
+                    .map(ToOwned::to_owned)
+                    .unwrap_or(field);
+                (name, message)
+            })
+            .collect();
+    }
+
+    error
+}
+
+/// Return the name of the field in Jira's API, if the declared field is one of the issue fields
+pub fn jira_field_name(issue_field: &IssueFieldConfig) -> Option<&str> {
+    let mut parts = issue_field.api_field.split('.');
+    match parts.next() {
+        Some("fields") => parts.next().map(|part| part.trim_end_matches("[]")),
+        _ => None,
+    }
+}
+
+/// Return the values of the declared field in the given issue fields (as returned by Jira's API),
+/// as they are written in the Kaiju code
+pub fn kaiju_field_values(
+    config: &Config,
+    issue_field: &IssueFieldConfig,
+    fields: &Value,
+) -> Result<Vec<String>> {
+    let path = issue_field
+        .api_field
+        .strip_prefix("fields.")
+        .with_context(|| format!("{} is not an issue field", issue_field.name))?;
+    let fields = fields.as_object().context("Failed to extract fields")?;
+    let values = get_in_fields(fields, path)?;
+
+    match &issue_field.values {
+        IssueFieldValuesConfig::Simple { .. } => Ok(values),
+        IssueFieldValuesConfig::FromBag { values_from } => {
+            let bag = config
+                .value_bag
+                .get(values_from)
+                .with_context(|| format!("Value bag {:?} not found", values_from))?;
+
+            Ok(values
+                .into_iter()
+                .map(|value| {
+                    bag.iter()
+                        .find(|&(_, bag_value)| bag_value == &value)
+                        .map(|(key, _)| key.clone())
+                        .unwrap_or(value)
+                })
+                .collect())
+        }
+    }
+}
+
+fn write_comments(contents: &mut String, comments: &[Comment]) -> Result<()> {
+    writeln!(contents)?;
+    writeln!(contents, "{}", COMMENTS_MARKER)?;
+    writeln!(contents, "{}", COMMENTS_HEADER)?;
+    writeln!(contents)?;
+
+    for comment in comments {
+        let date = comment.created.get(..10).unwrap_or(&comment.created);
+        writeln!(
+            contents,
+            "{} **{}** on {}:",
+            QUOTE_PREFIX, comment.author.display_name, date
+        )?;
+        for line in comment.body.lines() {
+            writeln!(contents, "{} {}", QUOTE_PREFIX, line)?;
+        }
+        writeln!(contents)?;
+    }
+
+    writeln!(contents, "{}", NEW_COMMENT_MARKER)?;
+
+    Ok(())
+}
+
+/// Find the transition with the given name among the available ones. The transitions in the config
+/// work as aliases, matching the one with the same id or to the same status
+pub fn find_transition<'a>(
+    config: &Config,
+    transitions: &'a [Transition],
+    name: &str,
+) -> Result<&'a Transition> {
+    transitions
+        .iter()
+        .find(|transition| transition.name == name)
+        .or_else(|| {
+            let alias = config
+                .transitions
+                .iter()
+                .find(|transition| transition.name == name)?;
+            transitions
+                .iter()
+                .find(|transition| transition.id == alias.id)
+                .or_else(|| {
+                    transitions
+                        .iter()
+                        .find(|transition| transition.to.id == alias.to_status_id)
+                })
+        })
+        .with_context(|| {
+            format!(
+                "Transition {} is not available for this issue. Valid ones are: {}",
+                name,
+                transitions
+                    .iter()
+                    .map(|transition| &transition.name)
+                    .format(", ")
+            )
+        })
+}
+
+/// Find the active or future sprint with the given name
+pub fn find_sprint<'a>(sprints: &'a [Sprint], name: &str) -> Result<&'a Sprint> {
+    sprints
+        .iter()
+        .find(|sprint| sprint.name == name)
+        .with_context(|| {
+            format!(
+                "Sprint {} is not known. Valid ones are: {}",
+                name,
+                sprints.iter().map(|sprint| &sprint.name).format(", ")
+            )
+        })
+}
+
+/// Remove from the commands the ones that set fields of the transition screen, by their name (like
+/// "Resolution") or id (like "resolution"), returning them in the format expected by Jira's API.
+/// Fields declared in the config are left to be edited as usual
+pub fn take_transition_fields(
+    config: &Config,
+    transition: &Transition,
+    commands: &mut BTreeMap<String, Vec<String>>,
+) -> Result<Map<String, Value>> {
+    let mut fields = Map::new();
+
+    for (id, field) in &transition.fields {
+        let is_issue_field = kaiju_field_name(config, id).is_some()
+            || config
+                .issue_fields
+                .iter()
+                .any(|issue_field| issue_field.name == field.name);
+        if is_issue_field {
+            continue;
+        }
+
+        let values = match commands.remove(&field.name).or_else(|| commands.remove(id)) {
+            None => {
+                ensure!(
+                    !field.required || field.has_default_value,
+                    "Transition {} requires the field {}",
+                    transition.name,
+                    field.name
+                );
+                continue;
+            }
+            Some(values) => values,
+        };
+
+        let value = match <[String; 1]>::try_from(values) {
+            Ok([value]) => value,
+            Err(_) => bail!("The field {} takes a single value", field.name),
+        };
+
+        let value = if field.allowed_values.is_empty() {
+            Value::String(value)
+        } else {
+            let allowed_value = field
+                .allowed_values
+                .iter()
+                .find(|allowed_value| allowed_value.label() == Some(&value))
+                .with_context(|| {
+                    format!(
+                        "{:?} is not a valid value for {}. Valid ones are: {}",
+                        value,
+                        field.name,
+                        field
+                            .allowed_values
+                            .iter()
+                            .filter_map(|allowed_value| allowed_value.label())
+                            .format(", ")
+                    )
+                })?;
+            match &allowed_value.id {
+                Some(id) => json!({ "id": id }),
+                None => json!({ "name": value }),
+            }
+        };
+
+        fields.insert(id.clone(), value);
+    }
+
+    Ok(fields)
+}
+
+/// Parse a duration like "1h30m" or "2d 4h" into the format expected by Jira, like "1h 30m"
+pub fn parse_duration(text: &str) -> Result<String> {
+    let mut parts = vec![];
+    let mut number = String::new();
+    for c in text.chars().filter(|c| !c.is_whitespace()) {
+        if c.is_ascii_digit() || c == '.' {
+            number.push(c);
+        } else {
+            ensure!(
+                matches!(c, 'w' | 'd' | 'h' | 'm'),
+                "Unknown unit {:?} in duration {:?}. Use w, d, h or m",
+                c,
+                text
+            );
+            ensure!(
+                !number.is_empty(),
+                "Missing amount before {:?} in {:?}",
+                c,
+                text
+            );
+            parts.push(format!("{}{}", number, c));
+            number.clear();
+        }
+    }
+
+    ensure!(
+        number.is_empty(),
+        "Missing unit after {:?} in {:?}",
+        number,
+        text
+    );
+    ensure!(!parts.is_empty(), "The duration can not be empty");
+
+    Ok(parts.join(" "))
+}
+
 fn write_default_kaiju_code<'a>(
     contents: &mut String,
     config: &Config,
@@ -198,12 +551,47 @@ fn write_kaiju_values<'a, 'b>(
     Ok(())
 }
 
-#[derive(Debug, Clone, Eq, PartialEq)]
+#[derive(Debug, Clone, Default, Eq, PartialEq)]
 pub struct CreateIssue {
     pub summary: String,
     pub description: String,
     pub transition: Option<String>,
     pub commands: BTreeMap<String, Vec<String>>,
+    pub work_logs: Vec<WorkLog>,
+    pub links: Vec<NewIssueLink>,
+    /// The summaries of the subtasks to create, from the checklist in the subtasks section
+    pub subtasks: Vec<String>,
+    /// The name of the sprint to move the issue to
+    pub sprint: Option<String>,
+    /// The text written after the quoted comments, to be added as a new comment
+    pub new_comment: Option<String>,
+}
+
+impl CreateIssue {
+    /// Replace the values of a Kaiju command, given like "Type=Bug" or "Subsystems=fire, water"
+    pub fn set_command(&mut self, assignment: &str) -> Result<()> {
+        let (name, value) = assignment.split_once('=').with_context(|| {
+            format!(
+                "Fields must have an equal sign (=) separating name and value in {:?}",
+                assignment
+            )
+        })?;
+        let name = name.trim();
+
+        if name == TRANSITION_COMMAND {
+            self.transition = Some(value.trim().to_owned());
+        } else {
+            self.commands.insert(
+                name.to_owned(),
+                value
+                    .split(',')
+                    .map(|value| value.trim().to_owned())
+                    .collect(),
+            );
+        }
+
+        Ok(())
+    }
 }
 
 pub fn parse_issue_markdown(source: &str) -> Result<CreateIssue> {
@@ -218,13 +606,49 @@ pub fn parse_issue_markdown(source: &str) -> Result<CreateIssue> {
         .to_owned();
 
     let mut description_lines = vec![];
+    let mut comment_lines = vec![];
     let mut commands: BTreeMap<_, Vec<_>> = BTreeMap::new();
     let mut is_kaiju_code = false;
     let mut has_kaiju_code = false;
+    let mut is_comments = false;
+    let mut is_new_comment = false;
     let mut transition = None;
+    let mut work_logs = vec![];
+    let mut links = vec![];
+    let mut subtasks = vec![];
+    let mut is_subtasks = false;
+    let mut sprint = None;
     for line in lines {
         let trimmed_line = line.trim();
-        if is_kaiju_code {
+        if is_subtasks {
+            // The section ends with the next header or the comments written by Kaiju after it
+            if trimmed_line.starts_with('#') || trimmed_line.starts_with("<!--") {
+                is_subtasks = false;
+            } else {
+                if !trimmed_line.is_empty() {
+                    subtasks.push(parse_subtask(trimmed_line)?);
+                }
+                continue;
+            }
+        }
+
+        if is_new_comment {
+            comment_lines.push(line);
+        } else if is_comments {
+            // Existing comments are quoted and can not be edited here
+            if trimmed_line == NEW_COMMENT_MARKER {
+                is_new_comment = true;
+            } else if !trimmed_line.starts_with(QUOTE_PREFIX) && trimmed_line != COMMENTS_HEADER {
+                comment_lines.push(line);
+            }
+        } else if is_kaiju_code && trimmed_line == SUBTASKS_HEADER {
+            // Only inside the Kaiju section, so that descriptions can have a section like this
+            is_kaiju_code = false;
+            is_subtasks = true;
+        } else if has_kaiju_code && trimmed_line == COMMENTS_MARKER {
+            is_kaiju_code = false;
+            is_comments = true;
+        } else if is_kaiju_code {
             if trimmed_line.starts_with("# ") {
                 is_kaiju_code = false;
                 description_lines.push(line);
@@ -245,6 +669,35 @@ pub fn parse_issue_markdown(source: &str) -> Result<CreateIssue> {
                         "The transition command can only be used once"
                     );
                     transition = Some(value.trim().to_string());
+                } else if command.trim() == SPRINT_COMMAND {
+                    ensure!(sprint.is_none(), "The sprint command can only be used once");
+                    sprint = Some(value.trim().to_string());
+                } else if command.trim() == LOG_COMMAND {
+                    let (time_spent, comment) = match value.split_once(',') {
+                        None => (value, None),
+                        Some((time_spent, comment)) => (time_spent, Some(comment.trim())),
+                    };
+                    work_logs.push(WorkLog {
+                        time_spent: parse_duration(time_spent)?,
+                        comment: comment
+                            .filter(|comment| !comment.is_empty())
+                            .map(ToOwned::to_owned),
+                    });
+                } else if let Some((_, link_type, outward)) = LINK_COMMANDS
+                    .iter()
+                    .find(|(link_command, _, _)| command.trim() == *link_command)
+                {
+                    links.extend(
+                        value
+                            .split(',')
+                            .map(str::trim)
+                            .filter(|key| !key.is_empty())
+                            .map(|key| NewIssueLink {
+                                link_type: link_type.to_string(),
+                                key: key.to_string(),
+                                outward: *outward,
+                            }),
+                    );
                 } else {
                     commands
                         .entry(command.trim().to_owned())
@@ -272,14 +725,49 @@ pub fn parse_issue_markdown(source: &str) -> Result<CreateIssue> {
         .trim()
         .to_owned();
 
+    let new_comment = comment_lines
+        .into_iter()
+        .format("\n")
+        .to_string()
+        .trim()
+        .to_owned();
+
     Ok(CreateIssue {
         summary,
         description,
         transition,
         commands,
+        work_logs,
+        links,
+        subtasks,
+        sprint,
+        new_comment: if new_comment.is_empty() {
+            None
+        } else {
+            Some(new_comment)
+        },
     })
 }
 
+/// Parse an item of the subtasks checklist, like "- [ ] Write migration", returning its summary
+fn parse_subtask(line: &str) -> Result<String> {
+    let summary = ["- [ ]", "- [x]", "- [X]"]
+        .iter()
+        .find_map(|prefix| line.strip_prefix(prefix))
+        .with_context(|| {
+            format!(
+                "Subtasks must be written like \"- [ ] Summary\", not {:?}",
+                line
+            )
+        })?
+        .trim();
+    ensure!(!summary.is_empty(), "Subtasks must have a summary");
+
+    Ok(summary.to_owned())
+}
+
+/// Return the body to create or edit the issue. Its links and subtasks are not included, because
+/// they must be created after the issue exists
 pub fn prepare_api_body(config: &Config, issue: CreateIssue) -> Result<Value> {
     let mut fields = Map::new();
 
@@ -294,6 +782,19 @@ pub fn prepare_api_body(config: &Config, issue: CreateIssue) -> Result<Value> {
     let mut body = Map::new();
     body.insert("fields".to_string(), Value::Object(fields));
 
+    let mut update = Map::new();
+    if !issue.work_logs.is_empty() {
+        let work_logs = issue
+            .work_logs
+            .iter()
+            .map(|work_log| json!({ "add": work_log }))
+            .collect_vec();
+        update.insert("worklog".to_string(), Value::Array(work_logs));
+    }
+    if !update.is_empty() {
+        body.insert("update".to_string(), Value::Object(update));
+    }
+
     if let Some(transition_name) = issue.transition {
         let transition = config
             .transitions
@@ -544,8 +1045,307 @@ even more description",
                         "command_2".to_string(),
                         vec!["value_20".to_string(), "value_21".to_string()]
                     )
-                ])
+                ]),
+                ..Default::default()
             }
         );
     }
+
+    #[test]
+    fn test_parse_log_command() {
+        let issue = parse_issue_markdown(
+            "# Summary
+# Kaiju
+Log: 1h30m, fixed flaky test, again
+Log: 2d
+<!--Log: 1h30m, what was done-->",
+        )
+        .unwrap();
+
+        assert_eq!(
+            issue.work_logs,
+            vec![
+                WorkLog {
+                    time_spent: "1h 30m".to_string(),
+                    comment: Some("fixed flaky test, again".to_string()),
+                },
+                WorkLog {
+                    time_spent: "2d".to_string(),
+                    comment: None,
+                },
+            ]
+        );
+        assert!(issue.commands.is_empty());
+        assert!(parse_issue_markdown("# Summary\n# Kaiju\nLog: soon\n").is_err());
+    }
+
+    #[test]
+    fn test_parse_links_sprint_and_subtasks() {
+        let issue = parse_issue_markdown(
+            "# Summary
+# Kaiju
+Sprint: Sprint 42
+Blocked by: WEB-12, WEB-13
+## Subtasks
+- [ ] Write migration
+- [x]  Update docs 
+",
+        )
+        .unwrap();
+
+        assert_eq!(issue.sprint.as_deref(), Some("Sprint 42"));
+        assert_eq!(
+            issue.links,
+            vec![
+                NewIssueLink {
+                    link_type: "Blocks".to_string(),
+                    key: "WEB-12".to_string(),
+                    outward: false,
+                },
+                NewIssueLink {
+                    link_type: "Blocks".to_string(),
+                    key: "WEB-13".to_string(),
+                    outward: false,
+                },
+            ]
+        );
+        assert_eq!(issue.subtasks, ["Write migration", "Update docs"]);
+    }
+
+    #[test]
+    fn test_parse_subtasks_only_in_kaiju_section() {
+        let mut config: Config =
+            toml::from_str(include_str!("../resources/default_config.toml")).unwrap();
+        config.issue_fields.clear();
+        let description = "Plan\n## Subtasks\n- [ ] Already created";
+
+        let mut code = edit_issue(
+            &config,
+            json!({"summary": "Summary", "description": description}),
+            &[],
+            &[],
+            None,
+        )
+        .unwrap();
+        let issue = parse_issue_markdown(&code).unwrap();
+        assert_eq!(issue.description, description);
+        assert!(issue.subtasks.is_empty());
+
+        code.push_str("## Subtasks\n- [ ] New one\n");
+        let issue = parse_issue_markdown(&code).unwrap();
+        assert_eq!(issue.description, description);
+        assert_eq!(issue.subtasks, ["New one"]);
+    }
+
+    #[test]
+    fn test_set_command() {
+        let mut issue = parse_issue_markdown("# Summary\n# Kaiju\nType: Story\n").unwrap();
+        issue.set_command("Type=Bug").unwrap();
+        issue.set_command("Subsystems = fire, water").unwrap();
+        issue.set_command("Transition=Review").unwrap();
+        assert!(issue.set_command("Type").is_err());
+
+        assert_eq!(issue.commands["Type"], ["Bug"]);
+        assert_eq!(issue.commands["Subsystems"], ["fire", "water"]);
+        assert_eq!(issue.transition.as_deref(), Some("Review"));
+    }
+
+    #[test]
+    fn test_prepare_links() {
+        let mut config: Config =
+            toml::from_str(include_str!("../resources/default_config.toml")).unwrap();
+        config.issue_fields.clear();
+
+        let issue = parse_issue_markdown("# Summary\n# Kaiju\nBlocks: WEB-12\n").unwrap();
+        assert_eq!(issue.links.len(), 1);
+        // Links are created with their own API, once the issue exists
+        let body = prepare_api_body(&config, issue).unwrap();
+        assert!(body.get("update").is_none());
+    }
+
+    #[test]
+    fn test_transitions() {
+        let config: Config =
+            toml::from_str(include_str!("../resources/default_config.toml")).unwrap();
+        let transitions = serde_json::from_value::<Vec<Transition>>(json!([
+            {"id": "20", "name": "Start", "to": {"id": "2", "name": "Implement"}},
+            {
+                "id": "40",
+                "name": "Close",
+                "to": {"id": "4", "name": "Done"},
+                "fields": {
+                    "resolution": {
+                        "name": "Resolution",
+                        "required": true,
+                        "allowedValues": [{"id": "1", "name": "Fixed"}, {"id": "2", "name": "Won't Do"}],
+                    },
+                },
+            },
+        ]))
+        .unwrap();
+
+        // By name, or by the id and target status of the aliases in the config
+        assert_eq!(
+            find_transition(&config, &transitions, "Start").unwrap().id,
+            "20"
+        );
+        assert_eq!(
+            find_transition(&config, &transitions, "Implement")
+                .unwrap()
+                .id,
+            "20"
+        );
+        assert!(find_transition(&config, &transitions, "Review").is_err());
+
+        let close = find_transition(&config, &transitions, "Close").unwrap();
+        let mut commands = BTreeMap::new();
+        assert!(take_transition_fields(&config, close, &mut commands).is_err());
+
+        commands.insert("Resolution".to_string(), vec!["Won't Do".to_string()]);
+        commands.insert("Other".to_string(), vec!["value".to_string()]);
+        let fields = take_transition_fields(&config, close, &mut commands).unwrap();
+        assert_eq!(Value::Object(fields), json!({"resolution": {"id": "2"}}));
+        assert_eq!(commands.keys().collect_vec(), ["Other"]);
+
+        commands.insert("resolution".to_string(), vec!["Fixed".to_string()]);
+        let fields = take_transition_fields(&config, close, &mut commands).unwrap();
+        assert_eq!(Value::Object(fields), json!({"resolution": {"id": "1"}}));
+        assert_eq!(commands.keys().collect_vec(), ["Other"]);
+    }
+
+    #[test]
+    fn test_split_issues_markdown() {
+        let issues = split_issues_markdown(
+            "# First\nTitle\n===\n# Kaiju\n<!-- kaiju:next-issue -->\n\n  <!-- kaiju:next-issue -->  \n\
+            # Second\n<!-- kaiju:next-issue -->\n",
+        );
+        assert_eq!(issues, ["# First\nTitle\n===\n# Kaiju\n", "# Second\n"]);
+
+        let mut config: Config =
+            toml::from_str(include_str!("../resources/default_config.toml")).unwrap();
+        config.issue_fields.clear();
+        let code = new_issues(&config).unwrap();
+        assert_eq!(split_issues_markdown(&code).len(), 2);
+    }
+
+    #[test]
+    fn test_parse_duration() {
+        assert_eq!(parse_duration("1h30m").unwrap(), "1h 30m");
+        assert_eq!(parse_duration(" 2d 4h ").unwrap(), "2d 4h");
+        assert_eq!(parse_duration("1.5h").unwrap(), "1.5h");
+        assert!(parse_duration("90").is_err());
+        assert!(parse_duration("1x").is_err());
+        assert!(parse_duration("").is_err());
+    }
+
+    #[test]
+    fn test_parse_new_comment() {
+        let mut config: Config =
+            toml::from_str(include_str!("../resources/default_config.toml")).unwrap();
+        config.issue_fields.clear();
+        let comments = serde_json::from_value::<Vec<Comment>>(json!([{
+            "id": "1",
+            "author": {"displayName": "Alice"},
+            "body": "First line\n> quoted line\n# Not a header",
+            "created": "2023-05-01T10:00:00.000+0000",
+            "updated": "2023-05-01T10:00:00.000+0000",
+        }]))
+        .unwrap();
+
+        let mut code = edit_issue(
+            &config,
+            json!({"summary": "Summary", "description": null}),
+            &[],
+            &[],
+            Some(&comments),
+        )
+        .unwrap();
+        assert!(code.contains("> **Alice** on 2023-05-01:\n> First line\n"));
+        assert_eq!(parse_issue_markdown(&code).unwrap().new_comment, None);
+
+        code.push_str("\nA new\ncomment\n");
+        let issue = parse_issue_markdown(&code).unwrap();
+        assert_eq!(issue.new_comment.as_deref(), Some("A new\ncomment"));
+        assert_eq!(issue.description, "");
+    }
+
+    #[test]
+    fn test_parse_subtasks_before_comments() {
+        let mut config: Config =
+            toml::from_str(include_str!("../resources/default_config.toml")).unwrap();
+        config.issue_fields.clear();
+        let comments = serde_json::from_value::<Vec<Comment>>(json!([{
+            "id": "1",
+            "author": {"displayName": "Alice"},
+            "body": "Old comment",
+            "created": "2023-05-01T10:00:00.000+0000",
+            "updated": "2023-05-01T10:00:00.000+0000",
+        }]))
+        .unwrap();
+
+        let mut code = edit_issue(
+            &config,
+            json!({"summary": "Summary", "description": "Plan"}),
+            &[],
+            &[],
+            Some(&comments),
+        )
+        .unwrap()
+        .replace(
+            COMMENTS_MARKER,
+            &format!("{}\n- [ ] New one\n\n{}", SUBTASKS_HEADER, COMMENTS_MARKER),
+        );
+        code.push_str("Thanks\n");
+        let issue = parse_issue_markdown(&code).unwrap();
+
+        assert_eq!(issue.subtasks, ["New one"]);
+        assert_eq!(issue.description, "Plan");
+        assert_eq!(issue.new_comment.as_deref(), Some("Thanks"));
+    }
+
+    #[test]
+    fn test_parse_comments_like_text() {
+        let mut config: Config =
+            toml::from_str(include_str!("../resources/default_config.toml")).unwrap();
+        config.issue_fields.clear();
+        let comments = serde_json::from_value::<Vec<Comment>>(json!([{
+            "id": "1",
+            "author": {"displayName": "Alice"},
+            "body": "Old comment",
+            "created": "2023-05-01T10:00:00.000+0000",
+            "updated": "2023-05-01T10:00:00.000+0000",
+        }]))
+        .unwrap();
+        let description = "Intro\n# Comments\n> A quote in the description";
+
+        let mut code = edit_issue(
+            &config,
+            json!({"summary": "Summary", "description": description}),
+            &[],
+            &[],
+            Some(&comments),
+        )
+        .unwrap();
+        code.push_str("> Quoting the old comment\n# Comments\nI agree\n");
+        let issue = parse_issue_markdown(&code).unwrap();
+
+        assert_eq!(issue.description, description);
+        assert_eq!(
+            issue.new_comment.as_deref(),
+            Some("> Quoting the old comment\n# Comments\nI agree")
+        );
+    }
+
+    #[test]
+    fn test_kaiju_field_name() {
+        let config: Config =
+            toml::from_str(include_str!("../resources/default_config.toml")).unwrap();
+
+        assert_eq!(kaiju_field_name(&config, "assignee"), Some("Assignee"));
+        assert_eq!(
+            kaiju_field_name(&config, "customfield_77"),
+            Some("Subsystems")
+        );
+        assert_eq!(kaiju_field_name(&config, "summary"), None);
+    }
 }
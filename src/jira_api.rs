@@ -1,10 +1,16 @@
-use crate::config::Config;
-use anyhow::Result;
-use reqwest::{Client, RequestBuilder};
+use crate::config::{Config, RetryConfig};
+use anyhow::{ensure, Context, Error, Result};
+use itertools::Itertools;
+use reqwest::header::RETRY_AFTER;
+use reqwest::{Client, Method, RequestBuilder, Response, StatusCode};
 use serde::de::DeserializeOwned;
-use serde::Deserialize;
-use serde_json::{json, Value};
-use std::time::Duration;
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Map, Value};
+use std::collections::BTreeMap;
+use std::fmt;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
 
 #[derive(Debug)]
 pub struct JiraApi {
@@ -12,61 +18,455 @@ pub struct JiraApi {
     api_host: String,
     email: String,
     token: String,
+    retry: Option<RetryConfig>,
+    rate_limited: AtomicBool,
+    subtask_issue_type: String,
 }
 
-#[derive(Debug, Clone, Deserialize)]
+/// How many issues to ask for in each page. Jira may answer with fewer
+const PAGE_SIZE: usize = 100;
+
+/// How many issues Jira accepts in each bulk creation
+const BULK_CREATE_SIZE: usize = 50;
+
+/// How many issues Jira accepts in each rank call
+const RANK_SIZE: usize = 50;
+
+/// Where to put ranked issues, relative to another issue
+#[derive(Debug, Clone, Copy)]
+pub enum RankPosition<'a> {
+    Before(&'a str),
+    After(&'a str),
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
 #[serde(rename_all = "camelCase")]
 pub struct BoardConfiguration {
     pub name: String,
+    /// Like "scrum" or "kanban"
+    #[serde(default, rename = "type")]
+    pub kind: Option<String>,
     pub column_config: ColumnsConfig,
 }
 
-#[derive(Debug, Clone, Deserialize)]
+#[derive(Debug, Clone, Deserialize, Serialize)]
 pub struct ColumnsConfig {
     pub columns: Vec<ColumnConfig>,
 }
 
-#[derive(Debug, Clone, Deserialize)]
+#[derive(Debug, Clone, Deserialize, Serialize)]
 pub struct ColumnConfig {
     pub name: String,
     pub statuses: Vec<ColumnStatus>,
 }
 
-#[derive(Debug, Clone, Deserialize)]
+#[derive(Debug, Clone, Deserialize, Serialize)]
 pub struct ColumnStatus {
     pub id: String,
 }
 
-#[derive(Debug, Clone, Deserialize)]
+#[derive(Debug, Clone, Deserialize, Serialize)]
 pub struct BoardIssues {
     pub issues: Vec<Issue>,
+    /// The total number of issues matching the query, which can be more than the ones loaded
+    #[serde(default)]
+    pub total: usize,
 }
 
+/// One page of issues from a paginated endpoint
 #[derive(Debug, Clone, Deserialize)]
+pub struct BoardIssuesPage {
+    issues: Vec<Issue>,
+    total: usize,
+}
+
+impl BoardIssuesPage {
+    /// Where the pages that follow this first one start, to get all issues or up to `max_issues`.
+    /// They have as many issues as this one, which can be less than asked for when Jira caps the
+    /// page size
+    pub fn other_pages(&self, max_issues: Option<usize>) -> impl Iterator<Item = usize> {
+        (self.issues.len()..self.num_wanted(max_issues)).step_by(self.issues.len().max(1))
+    }
+
+    /// Join this first page with the ones that follow it
+    pub fn join(self, other_pages: Vec<BoardIssuesPage>, max_issues: Option<usize>) -> BoardIssues {
+        let num_wanted = self.num_wanted(max_issues);
+        let mut issues = self.issues;
+        for page in other_pages {
+            issues.extend(page.issues);
+        }
+        issues.truncate(num_wanted);
+
+        BoardIssues {
+            issues,
+            total: self.total,
+        }
+    }
+
+    fn num_wanted(&self, max_issues: Option<usize>) -> usize {
+        max_issues.map_or(self.total, |max_issues| max_issues.min(self.total))
+    }
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
 pub struct Issue {
     pub id: String,
     pub key: String,
     pub fields: Value,
 }
 
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Sprint {
+    pub id: u64,
+    pub name: String,
+    /// Like "active" or "future"
+    pub state: String,
+    pub goal: Option<String>,
+    pub start_date: Option<String>,
+    pub end_date: Option<String>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct SprintsPage {
+    values: Vec<Sprint>,
+    #[serde(default)]
+    is_last: bool,
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct Transition {
+    pub id: String,
+    pub name: String,
+    pub to: TransitionTarget,
+    /// The fields in the transition screen, by id
+    #[serde(default)]
+    pub fields: BTreeMap<String, TransitionField>,
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct TransitionTarget {
+    pub id: String,
+    pub name: String,
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TransitionField {
+    pub name: String,
+    pub required: bool,
+    #[serde(default)]
+    pub has_default_value: bool,
+    #[serde(default)]
+    pub allowed_values: Vec<AllowedValue>,
+}
+
+/// An option of a field. Depending on the field, it is identified by its name or its value
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct AllowedValue {
+    pub id: Option<String>,
+    pub name: Option<String>,
+    pub value: Option<String>,
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct Comment {
+    pub id: String,
+    pub author: User,
+    pub body: String,
+    pub created: String,
+    pub updated: String,
+}
+
 #[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct CommentsPage {
+    comments: Vec<Comment>,
+    total: usize,
+}
+
+/// A link to add from an issue to another one
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct NewIssueLink {
+    /// The name of the Jira link type, like "Blocks"
+    pub link_type: String,
+    pub key: String,
+    /// Whether the other issue is on the outward side of the link, like the blocked one
+    pub outward: bool,
+}
+
+/// Time spent working on an issue
+#[derive(Debug, Clone, Eq, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct WorkLog {
+    /// In Jira's duration format, like "1h 30m"
+    pub time_spent: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub comment: Option<String>,
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct User {
+    pub display_name: String,
+    pub avatar_urls: Option<AvatarUrls>,
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct AvatarUrls {
+    #[serde(rename = "32x32")]
+    pub size_32: String,
+}
+
+#[derive(Debug, Clone, Default, Deserialize, Serialize)]
 pub struct DevelopmentInfo {
+    #[serde(default)]
     pub branches: Vec<Branch>,
-    #[serde(rename = "pullRequests")]
+    #[serde(default, rename = "pullRequests")]
     pub merge_requests: Vec<MergeRequest>,
+    #[serde(default)]
+    pub repositories: Vec<Repository>,
+    #[serde(default)]
+    pub builds: Vec<Build>,
+    #[serde(default)]
+    pub deployments: Vec<Deployment>,
 }
 
-#[derive(Debug, Clone, Deserialize)]
+impl DevelopmentInfo {
+    /// Merge the development info of several providers
+    pub fn merge(infos: impl IntoIterator<Item = DevelopmentInfo>) -> Self {
+        let mut merged = DevelopmentInfo::default();
+        for info in infos {
+            merged.branches.extend(info.branches);
+            merged.merge_requests.extend(info.merge_requests);
+            merged.repositories.extend(info.repositories);
+            merged.builds.extend(info.builds);
+            merged.deployments.extend(info.deployments);
+        }
+
+        // Different data types can return the same branches
+        merged.branches = merged
+            .branches
+            .into_iter()
+            .unique_by(|branch| branch.url.clone())
+            .collect();
+
+        merged
+    }
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
 pub struct Branch {
     pub name: String,
     pub url: String,
 }
 
-#[derive(Debug, Clone, Deserialize)]
+#[derive(Debug, Clone, Deserialize, Serialize)]
 pub struct MergeRequest {
     pub name: String,
     pub status: String,
     pub url: String,
+    pub source: Option<MergeRequestSource>,
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct MergeRequestSource {
+    pub branch: String,
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct Repository {
+    pub name: String,
+    #[serde(default)]
+    pub commits: Vec<Commit>,
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Commit {
+    pub display_id: String,
+    pub message: String,
+    pub url: String,
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct Build {
+    pub name: String,
+    pub url: String,
+    /// Like "SUCCESSFUL", "FAILED" or "IN_PROGRESS"
+    pub state: String,
+    #[serde(default)]
+    pub references: Vec<BuildReference>,
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct BuildReference {
+    #[serde(rename = "ref")]
+    pub git_ref: Option<GitRef>,
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct GitRef {
+    pub name: String,
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Deployment {
+    pub display_name: String,
+    pub url: String,
+    pub state: String,
+    pub environment: DeploymentEnvironment,
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DeploymentEnvironment {
+    pub display_name: String,
+}
+
+/// How a failed call to the Jira API, or to a forge, should be treated
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub enum FailureKind {
+    /// The call may succeed if retried later, like on timeouts, rate limiting or server errors
+    Retryable,
+    /// The call will fail again, like on authentication, permission or not found errors
+    Permanent,
+}
+
+/// An error answered by Jira, with the explanations it gave
+#[derive(Debug, Clone)]
+pub struct JiraError {
+    pub status: StatusCode,
+    pub messages: Vec<String>,
+    /// Errors about specific fields, indexed by their name in Jira's API
+    pub field_errors: BTreeMap<String, String>,
+}
+
+impl AllowedValue {
+    /// How the option is displayed to the user
+    pub fn label(&self) -> Option<&String> {
+        self.name.as_ref().or(self.value.as_ref())
+    }
+}
+
+impl FailureKind {
+    pub fn of(error: &Error) -> FailureKind {
+        if let Some(jira_error) = find_cause::<JiraError>(error) {
+            return Self::of_status(jira_error.status);
+        }
+
+        match find_cause::<reqwest::Error>(error) {
+            Some(error) if error.is_timeout() || error.is_connect() => FailureKind::Retryable,
+            Some(error) => error
+                .status()
+                .map_or(FailureKind::Permanent, Self::of_status),
+            None => FailureKind::Permanent,
+        }
+    }
+
+    fn of_status(status: StatusCode) -> FailureKind {
+        if status == StatusCode::TOO_MANY_REQUESTS || status.is_server_error() {
+            FailureKind::Retryable
+        } else {
+            FailureKind::Permanent
+        }
+    }
+}
+
+impl JiraError {
+    /// Find the error answered by Jira that caused this one, if any
+    pub fn find(error: &Error) -> Option<&JiraError> {
+        find_cause(error)
+    }
+
+    async fn from_response(response: Response) -> JiraError {
+        let status = response.status();
+        let text = response.text().await.unwrap_or_default();
+        JiraError::from_text(status, &text)
+    }
+
+    fn from_text(status: StatusCode, text: &str) -> JiraError {
+        let body: JiraErrorBody = serde_json::from_str(text).unwrap_or_else(|_| {
+            tracing::debug!("Jira answered {} with unexpected body {:?}", status, text);
+            JiraErrorBody::default()
+        });
+
+        JiraError::from_body(status, body)
+    }
+
+    fn from_body(status: StatusCode, body: JiraErrorBody) -> JiraError {
+        JiraError {
+            status,
+            messages: body.error_messages,
+            field_errors: body.errors,
+        }
+    }
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct JiraErrorBody {
+    #[serde(default)]
+    error_messages: Vec<String>,
+    #[serde(default)]
+    errors: BTreeMap<String, String>,
+}
+
+impl fmt::Display for JiraError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Jira answered {}", self.status)?;
+
+        let explanations = self
+            .messages
+            .iter()
+            .map(ToString::to_string)
+            .chain(
+                self.field_errors
+                    .iter()
+                    .map(|(field, message)| format!("{}: {}", field, message)),
+            )
+            .collect_vec();
+        if !explanations.is_empty() {
+            write!(f, " ({})", explanations.iter().format("; "))?;
+        }
+
+        Ok(())
+    }
+}
+
+impl std::error::Error for JiraError {}
+
+/// An error that is remembered by the cache and returned to all callers. The original error is its
+/// source, so the whole chain can still be inspected.
+#[derive(Debug, Clone)]
+pub struct SharedError(Arc<Error>);
+
+impl SharedError {
+    pub fn new(error: Arc<Error>) -> Self {
+        SharedError(error)
+    }
+}
+
+impl fmt::Display for SharedError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        // The wrapped error is displayed as the source
+        write!(f, "Cached error")
+    }
+}
+
+impl std::error::Error for SharedError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        let error: &(dyn std::error::Error + 'static) = (*self.0).as_ref();
+        Some(error)
+    }
+}
+
+/// Find a cause of the given type in the error chain, including in errors that were shared by the
+/// cache
+fn find_cause<E: std::error::Error + 'static>(error: &Error) -> Option<&E> {
+    error.chain().find_map(|cause| cause.downcast_ref::<E>())
 }
 
 impl JiraApi {
@@ -79,9 +479,21 @@ impl JiraApi {
             api_host: config.api_host.clone(),
             email: config.email.clone(),
             token: config.token.clone(),
+            retry: config.api_retry.clone(),
+            rate_limited: AtomicBool::new(false),
+            subtask_issue_type: config
+                .subtask_issue_type
+                .clone()
+                .unwrap_or_else(|| "Sub-task".to_owned()),
         }
     }
 
+    /// Return whether Jira answered with "429 Too Many Requests" since the last time this was
+    /// called
+    pub fn take_rate_limited(&self) -> bool {
+        self.rate_limited.swap(false, Ordering::Relaxed)
+    }
+
     pub async fn create_issue(&self, issue: &Value) -> Result<String> {
         #[derive(Debug, Deserialize)]
         struct Response {
@@ -100,6 +512,76 @@ impl JiraApi {
         Ok(response.key)
     }
 
+    /// Create many issues at once. Returns, in the same order, the key of each created issue or why
+    /// Jira refused to create it
+    pub async fn create_issues(&self, issues: &[Value]) -> Result<Vec<Result<String, JiraError>>> {
+        #[derive(Debug, Deserialize)]
+        struct Response {
+            #[serde(default)]
+            issues: Vec<CreatedIssue>,
+            #[serde(default)]
+            errors: Vec<ElementError>,
+        }
+
+        #[derive(Debug, Deserialize)]
+        struct CreatedIssue {
+            key: String,
+        }
+
+        #[derive(Debug, Deserialize)]
+        #[serde(rename_all = "camelCase")]
+        struct ElementError {
+            failed_element_number: usize,
+            element_errors: JiraErrorBody,
+            status: Option<u16>,
+        }
+
+        let mut results = Vec::with_capacity(issues.len());
+        for batch in issues.chunks(BULK_CREATE_SIZE) {
+            tracing::debug!("Create {} issues", batch.len());
+            let response = self
+                .send_unchecked(
+                    self.client
+                        .post(format!("{}/rest/api/2/issue/bulk", self.api_host))
+                        .json(&json!({ "issueUpdates": batch })),
+                )
+                .await?;
+
+            // When all issues fail, Jira answers with an error status, but the same body
+            let status = response.status();
+            let text = response.text().await?;
+            let body = match serde_json::from_str::<Response>(&text) {
+                Ok(body) if status.is_success() || !body.errors.is_empty() => body,
+                _ => return Err(JiraError::from_text(status, &text).into()),
+            };
+
+            let mut created = body.issues.into_iter();
+            let mut errors: BTreeMap<_, _> = body
+                .errors
+                .into_iter()
+                .map(|error| (error.failed_element_number, error))
+                .collect();
+            for index in 0..batch.len() {
+                let result = match errors.remove(&index) {
+                    Some(error) => Err(JiraError::from_body(
+                        error
+                            .status
+                            .and_then(|status| StatusCode::from_u16(status).ok())
+                            .unwrap_or(StatusCode::BAD_REQUEST),
+                        error.element_errors,
+                    )),
+                    None => Ok(created
+                        .next()
+                        .context("Jira did not answer the keys of all created issues")?
+                        .key),
+                };
+                results.push(result);
+            }
+        }
+
+        Ok(results)
+    }
+
     pub async fn edit_issue(&self, key: &str, issue: &Value) -> Result<()> {
         tracing::debug!("Edit issue {}: {}", key, issue);
 
@@ -111,24 +593,57 @@ impl JiraApi {
         .await
     }
 
-    pub async fn transition_issue(&self, key: &str, transition_id: &str) -> Result<()> {
+    /// Execute the transition, setting the given fields of its screen, if any
+    pub async fn transition_issue(
+        &self,
+        key: &str,
+        transition_id: &str,
+        fields: &Map<String, Value>,
+    ) -> Result<()> {
         tracing::debug!("Transition issue {} to {}", key, transition_id);
 
+        let mut body = json!({
+            "transition": {
+                "id": transition_id,
+            }
+        });
+        if !fields.is_empty() {
+            body["fields"] = Value::Object(fields.clone());
+        }
+
         self.request_no_output(
             self.client
                 .post(format!(
                     "{}/rest/api/2/issue/{}/transitions",
                     self.api_host, key
                 ))
-                .json(&json!({
-                    "transition": {
-                        "id": transition_id,
-                    }
-                })),
+                .json(&body),
         )
         .await
     }
 
+    /// Load the transitions that can be executed from the current status of the issue
+    pub async fn transitions(&self, key: &str) -> Result<Vec<Transition>> {
+        #[derive(Debug, Deserialize)]
+        struct Response {
+            transitions: Vec<Transition>,
+        }
+
+        tracing::debug!("Load transitions for {}", key);
+        let response: Response = self
+            .request(
+                self.client
+                    .get(format!(
+                        "{}/rest/api/2/issue/{}/transitions",
+                        self.api_host, key
+                    ))
+                    .query(&[("expand", "transitions.fields")]),
+            )
+            .await?;
+
+        Ok(response.transitions)
+    }
+
     pub async fn board_configuration(&self, id: &str) -> Result<BoardConfiguration> {
         tracing::debug!("Load board configuration for {}", id);
         self.request(self.client.get(format!(
@@ -138,30 +653,320 @@ impl JiraApi {
         .await
     }
 
-    pub async fn board_issues(&self, id: &str, fields: &str, jql: &str) -> Result<BoardIssues> {
-        tracing::debug!("Load board issues for {}", id);
+    /// Load the page of issues of the board that match the query starting at `start_at`, without
+    /// going past `max_issues`
+    pub async fn board_issues_page(
+        &self,
+        id: &str,
+        fields: &str,
+        jql: &str,
+        start_at: usize,
+        max_issues: Option<usize>,
+    ) -> Result<BoardIssuesPage> {
+        tracing::debug!("Load board issues for {} from {}", id, start_at);
+
+        let url = format!("{}/rest/agile/1.0/board/{}/issue", self.api_host, id);
+        self.issues_page(&url, fields, Some(jql), start_at, max_issues)
+            .await
+    }
+
+    /// Load the page of issues in the backlog of the board, ordered by rank, starting at
+    /// `start_at`, without going past `max_issues`
+    pub async fn backlog_issues_page(
+        &self,
+        id: &str,
+        fields: &str,
+        start_at: usize,
+        max_issues: Option<usize>,
+    ) -> Result<BoardIssuesPage> {
+        tracing::debug!("Load backlog issues for {} from {}", id, start_at);
+
+        let url = format!("{}/rest/agile/1.0/board/{}/backlog", self.api_host, id);
+        self.issues_page(&url, fields, None, start_at, max_issues)
+            .await
+    }
+
+    /// Rank the issues right before or after another one, keeping their order. Large lists are
+    /// ranked in many calls
+    pub async fn rank_issues(&self, keys: &[String], position: RankPosition<'_>) -> Result<()> {
+        tracing::debug!("Rank {} {:?}", keys.iter().format(", "), position);
+
+        let mut position: RankPosition = position;
+        for chunk in keys.chunks(RANK_SIZE) {
+            let mut body = json!({ "issues": chunk });
+            match position {
+                RankPosition::Before(key) => body["rankBeforeIssue"] = json!(key),
+                RankPosition::After(key) => body["rankAfterIssue"] = json!(key),
+            }
+
+            self.request_no_output(
+                self.client
+                    .put(format!("{}/rest/agile/1.0/issue/rank", self.api_host))
+                    .json(&body),
+            )
+            .await?;
+
+            // The next issues go after the ones just ranked
+            if let (RankPosition::After(_), Some(last_key)) = (position, chunk.last()) {
+                position = RankPosition::After(last_key);
+            }
+        }
+
+        Ok(())
+    }
+
+    async fn issues_page(
+        &self,
+        url: &str,
+        fields: &str,
+        jql: Option<&str>,
+        start_at: usize,
+        max_issues: Option<usize>,
+    ) -> Result<BoardIssuesPage> {
+        let max_results = match max_issues {
+            None => PAGE_SIZE,
+            Some(max_issues) => PAGE_SIZE.min(max_issues - start_at),
+        };
+        let mut request = self
+            .client
+            .get(url)
+            .query(&[("fields", fields)])
+            .query(&[("startAt", start_at), ("maxResults", max_results)]);
+        if let Some(jql) = jql {
+            request = request.query(&[("jql", jql)]);
+        }
+
+        self.request(request).await
+    }
+
+    pub async fn issue(&self, key: &str) -> Result<Issue> {
+        tracing::debug!("Load issue {}", key);
         self.request(
             self.client
-                .get(format!(
-                    "{}/rest/agile/1.0/board/{}/issue",
-                    self.api_host, id
+                .get(format!("{}/rest/api/2/issue/{}", self.api_host, key)),
+        )
+        .await
+    }
+
+    /// Load all comments of the issue, from the oldest to the newest
+    pub async fn comments(&self, key: &str) -> Result<Vec<Comment>> {
+        tracing::debug!("Load comments for {}", key);
+
+        let mut comments = vec![];
+        loop {
+            let page: CommentsPage = self
+                .request(
+                    self.client
+                        .get(format!(
+                            "{}/rest/api/2/issue/{}/comment",
+                            self.api_host, key
+                        ))
+                        .query(&[("orderBy", "created")])
+                        .query(&[("startAt", comments.len()), ("maxResults", PAGE_SIZE)]),
+                )
+                .await?;
+
+            let is_last_page =
+                page.comments.is_empty() || comments.len() + page.comments.len() >= page.total;
+            comments.extend(page.comments);
+            if is_last_page {
+                return Ok(comments);
+            }
+        }
+    }
+
+    pub async fn add_comment(&self, key: &str, body: &str) -> Result<()> {
+        tracing::debug!("Add comment to {}", key);
+
+        self.request_no_output(
+            self.client
+                .post(format!(
+                    "{}/rest/api/2/issue/{}/comment",
+                    self.api_host, key
                 ))
-                .query(&[("fields", fields), ("jql", jql)]),
+                .json(&json!({ "body": body })),
         )
         .await
     }
 
-    pub async fn issue(&self, key: &str) -> Result<Issue> {
-        tracing::debug!("Load issue {}", key);
-        self.request(
+    pub async fn edit_comment(&self, key: &str, id: &str, body: &str) -> Result<()> {
+        tracing::debug!("Edit comment {} of {}", id, key);
+
+        self.request_no_output(
             self.client
-                .get(format!("{}/rest/api/2/issue/{}", self.api_host, key)),
+                .put(format!(
+                    "{}/rest/api/2/issue/{}/comment/{}",
+                    self.api_host, key, id
+                ))
+                .json(&json!({ "body": body })),
+        )
+        .await
+    }
+
+    /// Load the active and future sprints of a scrum board
+    pub async fn sprints(&self, board_id: &str) -> Result<Vec<Sprint>> {
+        tracing::debug!("Load sprints for {}", board_id);
+
+        let mut sprints = vec![];
+        loop {
+            let page: SprintsPage = self
+                .request(
+                    self.client
+                        .get(format!(
+                            "{}/rest/agile/1.0/board/{}/sprint",
+                            self.api_host, board_id
+                        ))
+                        .query(&[("state", "active,future")])
+                        .query(&[("startAt", sprints.len()), ("maxResults", PAGE_SIZE)]),
+                )
+                .await?;
+
+            let is_last_page = page.is_last || page.values.is_empty();
+            sprints.extend(page.values);
+            if is_last_page {
+                return Ok(sprints);
+            }
+        }
+    }
+
+    pub async fn move_issues_to_sprint(&self, sprint_id: u64, keys: &[String]) -> Result<()> {
+        tracing::debug!("Move {} to sprint {}", keys.iter().format(", "), sprint_id);
+
+        self.request_no_output(
+            self.client
+                .post(format!(
+                    "{}/rest/agile/1.0/sprint/{}/issue",
+                    self.api_host, sprint_id
+                ))
+                .json(&json!({ "issues": keys })),
+        )
+        .await
+    }
+
+    pub async fn add_work_log(&self, key: &str, work_log: &WorkLog) -> Result<()> {
+        tracing::debug!("Log {} of work in {}", work_log.time_spent, key);
+
+        self.request_no_output(
+            self.client
+                .post(format!(
+                    "{}/rest/api/2/issue/{}/worklog",
+                    self.api_host, key
+                ))
+                .json(work_log),
         )
         .await
     }
 
-    pub async fn development_info(&self, issue_id: &str) -> Result<DevelopmentInfo> {
-        tracing::debug!("Load development info for {}", issue_id);
+    /// Add the links and the subtasks of a new or edited issue. Both are attempted and, if both
+    /// fail, both failures are reported
+    pub async fn link_issues_and_create_subtasks(
+        &self,
+        key: &str,
+        links: &[NewIssueLink],
+        subtasks: &[String],
+    ) -> Result<()> {
+        let links_result = self.link_issues(key, links).await;
+        let subtasks_result = self.create_subtasks(key, subtasks).await;
+
+        match (links_result, subtasks_result) {
+            (Err(links_error), Err(subtasks_error)) => {
+                Err(subtasks_error.context(format!("{:#}", links_error)))
+            }
+            (links_result, subtasks_result) => links_result.and(subtasks_result),
+        }
+    }
+
+    /// Create the subtasks of the given issue, in order and in the same project, trying all of them
+    /// even if some fail
+    pub async fn create_subtasks(&self, parent_key: &str, summaries: &[String]) -> Result<()> {
+        let (project, _) = parent_key
+            .split_once('-')
+            .with_context(|| format!("Failed to extract the project from {}", parent_key))?;
+
+        let mut failures = vec![];
+        for summary in summaries {
+            let body = json!({
+                "fields": {
+                    "project": { "key": project },
+                    "parent": { "key": parent_key },
+                    "issuetype": { "name": self.subtask_issue_type },
+                    "summary": summary,
+                }
+            });
+            match self.create_issue(&body).await {
+                Ok(key) => tracing::info!("Created subtask: {}/browse/{}", self.api_host, key),
+                Err(error) => failures.push(format!("{:?}: {:#}", summary, error)),
+            }
+        }
+
+        ensure!(
+            failures.is_empty(),
+            "Created {} but failed to create {} of its {} subtasks: {}",
+            parent_key,
+            failures.len(),
+            summaries.len(),
+            failures.join("; ")
+        );
+
+        Ok(())
+    }
+
+    /// Link the issue to others, trying all links even if some fail
+    pub async fn link_issues(&self, key: &str, links: &[NewIssueLink]) -> Result<()> {
+        let mut failures = vec![];
+        for link in links {
+            tracing::debug!("Link {} to {} ({:?})", key, link.key, link);
+
+            // The inward issue relates to the outward one with the outward description, like
+            // "blocks"
+            let (inward_key, outward_key) = if link.outward {
+                (key, link.key.as_str())
+            } else {
+                (link.key.as_str(), key)
+            };
+            let body = json!({
+                "type": { "name": link.link_type },
+                "inwardIssue": { "key": inward_key },
+                "outwardIssue": { "key": outward_key },
+            });
+
+            let result = self
+                .request_no_output(
+                    self.client
+                        .post(format!("{}/rest/api/2/issueLink", self.api_host))
+                        .json(&body),
+                )
+                .await;
+            if let Err(error) = result {
+                failures.push(format!("{}: {:#}", link.key, error));
+            }
+        }
+
+        ensure!(
+            failures.is_empty(),
+            "Failed to create {} of the {} links of {}: {}",
+            failures.len(),
+            links.len(),
+            key,
+            failures.join("; ")
+        );
+
+        Ok(())
+    }
+
+    /// Load the development info of one application and data type
+    pub async fn development_info(
+        &self,
+        issue_id: &str,
+        application: &str,
+        data_type: &str,
+    ) -> Result<DevelopmentInfo> {
+        tracing::debug!(
+            "Load development info for {} from {} ({})",
+            issue_id,
+            application,
+            data_type
+        );
 
         #[derive(Debug, Deserialize)]
         struct Response {
@@ -177,44 +982,208 @@ impl JiraApi {
                     ))
                     .query(&[
                         ("issueId", issue_id),
-                        ("applicationType", "githube"),
-                        ("dataType", "pullrequest"),
+                        ("applicationType", application),
+                        ("dataType", data_type),
                     ]),
             )
             .await?;
 
-        let mut branches = vec![];
-        let mut merge_requests = vec![];
-        for detail in response.detail {
-            branches.extend(detail.branches);
-            merge_requests.extend(detail.merge_requests);
-        }
-
-        Ok(DevelopmentInfo {
-            branches,
-            merge_requests,
-        })
+        Ok(DevelopmentInfo::merge(response.detail))
     }
 
     async fn request<T: DeserializeOwned>(&self, request: RequestBuilder) -> Result<T> {
-        let response = request
-            .basic_auth(&self.email, Some(&self.token))
-            .send()
-            .await?
-            .error_for_status()?
-            .json()
-            .await?;
+        let response = self.send(request).await?.json().await?;
 
         Ok(response)
     }
 
     async fn request_no_output(&self, request: RequestBuilder) -> Result<()> {
-        request
-            .basic_auth(&self.email, Some(&self.token))
-            .send()
-            .await?
-            .error_for_status()?;
+        self.send(request).await?;
 
         Ok(())
     }
+
+    /// Send the request, retrying it with an exponential backoff if it is idempotent and failed
+    /// for a transient reason
+    async fn send(&self, request: RequestBuilder) -> Result<Response> {
+        Self::check_status(self.send_unchecked(request).await?).await
+    }
+
+    /// Like `send`, but hands back the final response whatever its status, for the few endpoints
+    /// that answer something useful along with an error status
+    async fn send_unchecked(&self, request: RequestBuilder) -> Result<Response> {
+        let request = request.basic_auth(&self.email, Some(&self.token)).build()?;
+
+        let retry = match &self.retry {
+            Some(retry) if request.method() == Method::GET => retry,
+            _ => return Ok(self.client.execute(request).await?),
+        };
+
+        let mut attempt = 1;
+        loop {
+            let cloned_request = request
+                .try_clone()
+                .context("Failed to clone request for a retry")?;
+            let result = self.client.execute(cloned_request).await;
+
+            let retry_after = match &result {
+                Ok(response) if response.status() == StatusCode::TOO_MANY_REQUESTS => {
+                    self.rate_limited.store(true, Ordering::Relaxed);
+                    Some(Self::retry_after(response))
+                }
+                Ok(response) if response.status().is_server_error() => None,
+                Err(error) if error.is_timeout() || error.is_connect() => None,
+                _ => return Ok(result?),
+            };
+
+            let sleep_time = match Self::retry_delay(retry, attempt, retry_after.flatten()) {
+                None => return Ok(result?),
+                Some(sleep_time) => sleep_time,
+            };
+            tracing::info!(
+                "Request to {} failed (attempt {}), will retry in {:.1}s",
+                request.url(),
+                attempt,
+                sleep_time.as_secs_f64()
+            );
+            tokio::time::sleep(sleep_time).await;
+            attempt += 1;
+        }
+    }
+
+    async fn check_status(response: Response) -> Result<Response> {
+        if response.status().is_success() {
+            Ok(response)
+        } else {
+            Err(JiraError::from_response(response).await.into())
+        }
+    }
+
+    /// Parse the "Retry-After" header, when given in seconds
+    fn retry_after(response: &Response) -> Option<Duration> {
+        let value = response.headers().get(RETRY_AFTER)?.to_str().ok()?;
+        let seconds = value.trim().parse().ok()?;
+        Some(Duration::from_secs(seconds))
+    }
+
+    /// How long to wait before retrying after the given attempt failed, or `None` if the retry
+    /// budget is spent. The time asked by Jira is honored, but never longer than the maximum backoff,
+    /// so that a board is not stalled for minutes
+    fn retry_delay(
+        retry: &RetryConfig,
+        attempt: u32,
+        retry_after: Option<Duration>,
+    ) -> Option<Duration> {
+        if attempt >= retry.max_attempts {
+            return None;
+        }
+
+        Some(match retry_after {
+            Some(retry_after) => retry_after.min(Duration::from_millis(retry.max_backoff_ms)),
+            None => Self::backoff(retry, attempt),
+        })
+    }
+
+    /// The exponential backoff for the given attempt, with up to 50% of random jitter
+    fn backoff(retry: &RetryConfig, attempt: u32) -> Duration {
+        let exponential = retry
+            .initial_backoff_ms
+            .saturating_mul(1 << (attempt - 1).min(16));
+        let backoff = exponential.min(retry.max_backoff_ms);
+
+        // Use the clock as a cheap source of randomness
+        let nanos = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map_or(0, |now| now.subsec_nanos() as u64);
+        let jitter = nanos % (backoff / 2 + 1);
+
+        Duration::from_millis(backoff - jitter)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn find_causes_in_shared_errors() {
+        let jira_error = JiraError {
+            status: StatusCode::SERVICE_UNAVAILABLE,
+            messages: vec![],
+            field_errors: BTreeMap::new(),
+        };
+        let shared = Error::new(SharedError::new(Arc::new(Error::new(jira_error))))
+            .context("Failed to load board");
+
+        assert_eq!(
+            JiraError::find(&shared).map(|error| error.status),
+            Some(StatusCode::SERVICE_UNAVAILABLE)
+        );
+        assert_eq!(FailureKind::of(&shared), FailureKind::Retryable);
+    }
+
+    #[test]
+    fn pages_follow_the_size_of_the_first_one() {
+        let page = |num_issues: usize, total: usize| BoardIssuesPage {
+            issues: (0..num_issues)
+                .map(|i| Issue {
+                    id: i.to_string(),
+                    key: format!("WEB-{}", i),
+                    fields: Value::Null,
+                })
+                .collect(),
+            total,
+        };
+
+        // Asked for 100 issues per page, but Jira answered only 50
+        let first_page = page(50, 120);
+        assert_eq!(first_page.other_pages(None).collect_vec(), [50, 100]);
+        assert_eq!(first_page.other_pages(Some(70)).collect_vec(), [50]);
+
+        let issues = first_page.join(vec![page(50, 120), page(20, 120)], None);
+        assert_eq!(issues.issues.len(), 120);
+        assert!(page(0, 0).other_pages(None).next().is_none());
+    }
+
+    #[test]
+    fn backoff_grows_until_the_limit() {
+        let retry = RetryConfig {
+            max_attempts: 10,
+            initial_backoff_ms: 100,
+            max_backoff_ms: 1000,
+            adaptive_parallelism: false,
+        };
+
+        let first = JiraApi::backoff(&retry, 1);
+        assert!(first >= Duration::from_millis(50) && first <= Duration::from_millis(100));
+
+        let third = JiraApi::backoff(&retry, 3);
+        assert!(third >= Duration::from_millis(200) && third <= Duration::from_millis(400));
+
+        let last = JiraApi::backoff(&retry, 40);
+        assert!(last >= Duration::from_millis(500) && last <= Duration::from_millis(1000));
+    }
+
+    #[test]
+    fn retry_after_is_capped() {
+        let retry = RetryConfig {
+            max_attempts: 3,
+            initial_backoff_ms: 100,
+            max_backoff_ms: 1000,
+            adaptive_parallelism: false,
+        };
+
+        assert_eq!(
+            JiraApi::retry_delay(&retry, 1, Some(Duration::from_millis(300))),
+            Some(Duration::from_millis(300))
+        );
+        assert_eq!(
+            JiraApi::retry_delay(&retry, 2, Some(Duration::from_secs(600))),
+            Some(Duration::from_millis(1000))
+        );
+        assert_eq!(
+            JiraApi::retry_delay(&retry, 3, Some(Duration::from_secs(1))),
+            None
+        );
+    }
 }
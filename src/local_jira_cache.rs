@@ -1,39 +1,140 @@
-use crate::config::CacheConfig;
-use crate::jira_api::{BoardConfiguration, BoardIssues, DevelopmentInfo, Issue, JiraApi};
-use anyhow::{anyhow, Context, Result};
+use crate::config::{CacheConfig, DevelopmentInfoProviders};
+use crate::forge_api::{ForgeApi, MergeRequestStatus};
+use crate::jira_api::{
+    BoardConfiguration, BoardIssues, BoardIssuesPage, Comment, DevelopmentInfo, FailureKind, Issue,
+    JiraApi, SharedError, Sprint, Transition,
+};
+use anyhow::{Context, Error, Result};
+use directories::ProjectDirs;
+use futures::future;
+use itertools::Itertools;
 use parking_lot::Mutex;
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
 use std::any::Any;
-use std::collections::hash_map::Entry;
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap, VecDeque};
+use std::fmt::Debug;
+use std::fs;
 use std::future::Future;
+use std::io::ErrorKind;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
 use std::sync::Arc;
-use std::time::{Duration, Instant};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
 use tokio::sync::{Notify, Semaphore};
+use tokio::task::JoinHandle;
 
 #[derive(Debug)]
 pub struct LocalJiraCache {
     api: Arc<JiraApi>,
-    data: Mutex<HashMap<CacheKey, CacheEntry>>,
+    data: Mutex<CacheData>,
     semaphore: Semaphore,
+    /// Limits the requests to the Git forges separately, so that slow forges don't hold back the
+    /// requests to Jira nor change their parallelism
+    forge_semaphore: Semaphore,
     config: CacheConfig,
+    persistence_path: PathBuf,
+    stats: Mutex<HashMap<&'static str, KindCounters>>,
+    waiting_for_permit: AtomicUsize,
+    max_parallelism: usize,
+    /// The number of permits in the semaphore, which can be reduced when Jira rate limits us
+    parallelism: AtomicUsize,
+    adaptive_parallelism: bool,
+    loads_since_rate_limited: AtomicUsize,
 }
 
-#[derive(Debug, Clone, Hash, Eq, PartialEq)]
+/// How many loads must succeed without being rate limited before parallelism is increased again
+const LOADS_TO_INCREASE_PARALLELISM: usize = 20;
+
+/// A snapshot of how the cache is behaving
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CacheStats {
+    pub num_entries: usize,
+    pub size_bytes: usize,
+    pub parallelism: usize,
+    pub available_permits: usize,
+    pub waiting_for_permit: usize,
+    pub kinds: BTreeMap<String, CacheKindStats>,
+    pub loading: Vec<String>,
+}
+
+/// The statistics for one kind of cached value
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct CacheKindStats {
+    pub entries: usize,
+    pub hits: u64,
+    pub stale_hits: u64,
+    /// Requests that waited for a load started by another request
+    pub coalesced: u64,
+    pub misses: u64,
+    pub errors: u64,
+    pub in_flight: usize,
+    /// How long a single request took, without the wait for its permit
+    pub average_latency_ms: Option<f64>,
+}
+
+#[derive(Debug, Default)]
+struct KindCounters {
+    hits: u64,
+    stale_hits: u64,
+    coalesced: u64,
+    misses: u64,
+    errors: u64,
+    requests: u64,
+    total_latency: Duration,
+}
+
+#[derive(Debug, Clone, Hash, Eq, PartialEq, Serialize, Deserialize)]
 enum CacheKey {
     BoardConfiguration {
         id: String,
     },
+    Sprints {
+        board_id: String,
+    },
+    Backlog {
+        id: String,
+        fields: String,
+        max_issues: Option<usize>,
+    },
     BoardIssues {
         id: String,
         fields: String,
         jql: String,
+        max_issues: Option<usize>,
     },
     Issue {
         key: String,
     },
+    Comments {
+        key: String,
+    },
+    /// The available transitions depend on the current status of the issue
+    Transitions {
+        key: String,
+        status_id: String,
+    },
     DevelopmentInfo {
         issue_id: String,
+        #[serde(default)]
+        providers: DevelopmentInfoProviders,
     },
+    MergeRequestStatus {
+        url: String,
+    },
+}
+
+/// The cached entries, tracking their usage to evict the least recently used ones quickly
+#[derive(Debug, Default)]
+struct CacheData {
+    entries: HashMap<CacheKey, CacheEntry>,
+    /// The loaded entries, from the least to the most recently used. An entry is pushed again
+    /// every time it is used, so older copies with a different generation are skipped
+    usage: VecDeque<(u64, CacheKey)>,
+    next_generation: u64,
+    num_loaded: usize,
+    size_bytes: usize,
 }
 
 #[derive(Debug)]
@@ -45,7 +146,17 @@ enum CacheEntry {
 #[derive(Debug)]
 struct CachedBox {
     live_until: Instant,
-    value: Result<Box<dyn Any + Send + Sync + 'static>>,
+    /// Until when this value can be served while a new one is loaded
+    stale_until: Instant,
+    /// Whether this value was restored from disk, in which case it can be served even after it
+    /// expires
+    restored: bool,
+    /// Whether a new value is being loaded to replace this one
+    refreshing: bool,
+    /// When this value was last used, as a generation of [`CacheData::usage`]
+    generation: u64,
+    size_bytes: usize,
+    value: Result<Arc<dyn CachedValue>, Arc<Error>>,
 }
 
 #[derive(Debug)]
@@ -53,15 +164,83 @@ enum CacheEntryState<T> {
     Miss,
     Loading(Arc<Notify>),
     Hit(Result<T>),
+    Stale(Result<T>),
+}
+
+/// A type-erased value that can be stored in the cache and saved to disk
+trait CachedValue: Any + Debug + Send + Sync {
+    fn as_any(&self) -> &dyn Any;
+
+    fn to_json(&self) -> Result<Value>;
+
+    /// The size of the value when serialized, as a proxy for how much memory it uses
+    fn approximate_size(&self) -> usize;
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct PersistedEntry {
+    key: CacheKey,
+    /// The expiration time, in seconds since the Unix epoch
+    live_until: u64,
+    value: Value,
 }
 
 impl LocalJiraCache {
-    pub fn new(api: Arc<JiraApi>, parallelism: usize, config: CacheConfig) -> Self {
-        LocalJiraCache {
+    pub fn new(
+        api: Arc<JiraApi>,
+        parallelism: usize,
+        adaptive_parallelism: bool,
+        config: CacheConfig,
+        project_dirs: &ProjectDirs,
+    ) -> Self {
+        let cache = LocalJiraCache {
             api,
             semaphore: Semaphore::new(parallelism),
+            forge_semaphore: Semaphore::new(parallelism),
             data: Default::default(),
             config,
+            persistence_path: project_dirs.cache_dir().join("jira_cache.json"),
+            stats: Default::default(),
+            waiting_for_permit: AtomicUsize::new(0),
+            max_parallelism: parallelism,
+            parallelism: AtomicUsize::new(parallelism),
+            adaptive_parallelism,
+            loads_since_rate_limited: AtomicUsize::new(0),
+        };
+
+        if cache.config.persistence.is_some() {
+            if let Err(error) = cache.restore() {
+                tracing::warn!("Failed to restore cache from disk: {:?}", error);
+            }
+        }
+
+        cache
+    }
+
+    /// Start the tasks that should run in the background while the cache is alive
+    pub fn start_background_tasks(self: &Arc<Self>) {
+        if let Some(persistence) = &self.config.persistence {
+            let inner = self.clone();
+            let interval = Duration::from_secs(persistence.save_interval_seconds);
+            tokio::spawn(async move {
+                loop {
+                    tokio::time::sleep(interval).await;
+                    if let Err(error) = inner.persist().await {
+                        tracing::warn!("Failed to persist cache to disk: {:?}", error);
+                    }
+                }
+            });
+        }
+
+        if let Some(sweep_interval_seconds) = self.config.sweep_interval_seconds {
+            let inner = self.clone();
+            let interval = Duration::from_secs(sweep_interval_seconds);
+            tokio::spawn(async move {
+                loop {
+                    tokio::time::sleep(interval).await;
+                    inner.sweep();
+                }
+            });
         }
     }
 
@@ -74,20 +253,76 @@ impl LocalJiraCache {
         .await
     }
 
+    pub async fn sprints(self: &Arc<Self>, board_id: String) -> Result<Vec<Sprint>> {
+        let time_to_live = self
+            .config
+            .ttl_sprints_seconds
+            .unwrap_or(self.config.ttl_board_configuration_seconds);
+
+        self.get(
+            CacheKey::Sprints {
+                board_id: board_id.clone(),
+            },
+            Duration::from_secs(time_to_live),
+            |api| async move { api.sprints(&board_id).await },
+        )
+        .await
+    }
+
     pub async fn board_issues(
         self: &Arc<Self>,
         id: String,
         fields: String,
         jql: String,
+        max_issues: Option<usize>,
     ) -> Result<BoardIssues> {
-        self.get(
-            CacheKey::BoardIssues {
-                id: id.to_owned(),
-                fields: fields.to_owned(),
-                jql: jql.to_owned(),
+        let key = CacheKey::BoardIssues {
+            id: id.to_owned(),
+            fields: fields.to_owned(),
+            jql: jql.to_owned(),
+            max_issues,
+        };
+        let kind = key.kind();
+        self.get_with(
+            key,
+            Duration::from_secs(self.config.ttl_board_issues_seconds),
+            move |cache| async move {
+                cache
+                    .all_issues(kind, max_issues, |start_at| {
+                        cache
+                            .api
+                            .board_issues_page(&id, &fields, &jql, start_at, max_issues)
+                    })
+                    .await
             },
+        )
+        .await
+    }
+
+    pub async fn backlog_issues(
+        self: &Arc<Self>,
+        id: String,
+        fields: String,
+        max_issues: Option<usize>,
+    ) -> Result<BoardIssues> {
+        let key = CacheKey::Backlog {
+            id: id.clone(),
+            fields: fields.clone(),
+            max_issues,
+        };
+        let kind = key.kind();
+        self.get_with(
+            key,
             Duration::from_secs(self.config.ttl_board_issues_seconds),
-            |api| async move { api.board_issues(&id, &fields, &jql).await },
+            move |cache| async move {
+                cache
+                    .all_issues(kind, max_issues, |start_at| {
+                        cache
+                            .api
+                            .backlog_issues_page(&id, &fields, start_at, max_issues)
+                    })
+                    .await
+            },
         )
         .await
     }
@@ -103,6 +338,36 @@ impl LocalJiraCache {
         .await
     }
 
+    pub async fn comments(self: &Arc<Self>, key: String) -> Result<Vec<Comment>> {
+        self.get(
+            CacheKey::Comments { key: key.clone() },
+            Duration::from_secs(self.config.ttl_issue_seconds),
+            |api| async move { api.comments(&key).await },
+        )
+        .await
+    }
+
+    pub async fn transitions(
+        self: &Arc<Self>,
+        key: String,
+        status_id: String,
+    ) -> Result<Vec<Transition>> {
+        let time_to_live = self
+            .config
+            .ttl_transitions_seconds
+            .unwrap_or(self.config.ttl_board_configuration_seconds);
+
+        self.get(
+            CacheKey::Transitions {
+                key: key.clone(),
+                status_id,
+            },
+            Duration::from_secs(time_to_live),
+            |api| async move { api.transitions(&key).await },
+        )
+        .await
+    }
+
     pub async fn epic(self: &Arc<Self>, key: String) -> Result<Issue> {
         self.get(
             CacheKey::Issue {
@@ -114,24 +379,371 @@ impl LocalJiraCache {
         .await
     }
 
-    pub async fn development_info(self: &Arc<Self>, issue_id: String) -> Result<DevelopmentInfo> {
-        self.get(
-            CacheKey::DevelopmentInfo {
-                issue_id: issue_id.to_owned(),
-            },
+    /// Load the development info from all the given providers, merging the ones that succeed. It
+    /// only fails if all providers fail
+    pub async fn development_info(
+        self: &Arc<Self>,
+        issue_id: String,
+        providers: DevelopmentInfoProviders,
+    ) -> Result<DevelopmentInfo> {
+        let key = CacheKey::DevelopmentInfo {
+            issue_id: issue_id.to_owned(),
+            providers: providers.clone(),
+        };
+        let kind = key.kind();
+        self.get_with(
+            key,
             Duration::from_secs(self.config.ttl_development_info_seconds),
-            |api| async move { api.development_info(&issue_id).await },
+            move |cache| async move {
+                let (cache, issue_id) = (&cache, &issue_id);
+                let results = future::join_all(
+                    providers
+                        .applications
+                        .iter()
+                        .cartesian_product(&providers.data_types)
+                        .map(|(application, data_type)| async move {
+                            let result = cache
+                                .limited(
+                                    kind,
+                                    cache.api.development_info(issue_id, application, data_type),
+                                )
+                                .await;
+                            (application, data_type, result)
+                        }),
+                )
+                .await;
+
+                let mut infos = vec![];
+                let mut first_error = None;
+                for (application, data_type, result) in results {
+                    match result {
+                        Ok(info) => infos.push(info),
+                        Err(error) => {
+                            tracing::warn!(
+                                "Failed to load development info for {} from {} ({}): {:#}",
+                                issue_id,
+                                application,
+                                data_type,
+                                error
+                            );
+                            first_error.get_or_insert(error);
+                        }
+                    }
+                }
+
+                match first_error {
+                    Some(error) if infos.is_empty() => Err(error),
+                    _ => Ok(DevelopmentInfo::merge(infos)),
+                }
+            },
+        )
+        .await
+    }
+
+    pub async fn merge_request_status(
+        self: &Arc<Self>,
+        forge_api: Arc<ForgeApi>,
+        url: String,
+    ) -> Result<MergeRequestStatus> {
+        let time_to_live = self
+            .config
+            .ttl_merge_request_status_seconds
+            .unwrap_or(self.config.ttl_development_info_seconds);
+
+        let key = CacheKey::MergeRequestStatus { url: url.clone() };
+        let kind = key.kind();
+        self.get_with(
+            key,
+            Duration::from_secs(time_to_live),
+            move |cache| async move {
+                let _permit = cache.forge_semaphore.acquire().await.unwrap();
+                cache
+                    .timed(kind, forge_api.merge_request_status(&url))
+                    .await
+            },
         )
         .await
     }
 
-    /// Remove all loaded entries from the cache
-    pub fn clear(&self) {
-        self.data
+    /// Load the development info of many issues concurrently, with one request per issue bounded by
+    /// the parallelism. Each issue is loaded only once, even if it is repeated
+    pub async fn development_infos(
+        self: &Arc<Self>,
+        issue_ids: Vec<String>,
+        providers: &DevelopmentInfoProviders,
+    ) -> HashMap<String, Result<DevelopmentInfo>> {
+        let issue_ids = issue_ids.into_iter().unique().collect_vec();
+        let infos = future::join_all(
+            issue_ids
+                .iter()
+                .map(|issue_id| self.development_info(issue_id.clone(), providers.clone())),
+        )
+        .await;
+
+        issue_ids.into_iter().zip(infos).collect()
+    }
+
+    /// Load all issues from a paginated endpoint, or up to `max_issues`. After the first page
+    /// tells how many issues there are, the others are loaded concurrently, each one waiting for
+    /// its own permit
+    async fn all_issues<P, F>(
+        self: &Arc<Self>,
+        kind: &'static str,
+        max_issues: Option<usize>,
+        page: P,
+    ) -> Result<BoardIssues>
+    where
+        P: Fn(usize) -> F,
+        F: Future<Output = Result<BoardIssuesPage>>,
+    {
+        let first_page = self.limited(kind, page(0)).await?;
+        let other_pages = future::try_join_all(
+            first_page
+                .other_pages(max_issues)
+                .map(|start_at| self.limited(kind, page(start_at))),
+        )
+        .await?;
+
+        Ok(first_page.join(other_pages, max_issues))
+    }
+
+    pub fn stats(&self) -> CacheStats {
+        let mut kinds: BTreeMap<String, CacheKindStats> = BTreeMap::new();
+        for (&kind, counters) in self.stats.lock().iter() {
+            let average_latency_ms = if counters.requests == 0 {
+                None
+            } else {
+                Some(counters.total_latency.as_secs_f64() * 1e3 / counters.requests as f64)
+            };
+
+            kinds.insert(
+                kind.to_owned(),
+                CacheKindStats {
+                    hits: counters.hits,
+                    stale_hits: counters.stale_hits,
+                    coalesced: counters.coalesced,
+                    misses: counters.misses,
+                    errors: counters.errors,
+                    average_latency_ms,
+                    ..Default::default()
+                },
+            );
+        }
+
+        let data = self.data.lock();
+        let mut loading = vec![];
+        for (key, value) in data.entries.iter() {
+            let kind_stats = kinds.entry(key.kind().to_owned()).or_default();
+            match value {
+                CacheEntry::Loading(_) => {
+                    kind_stats.in_flight += 1;
+                    loading.push(format!("{:?}", key));
+                }
+                CacheEntry::Loaded(cached) => {
+                    kind_stats.entries += 1;
+                    if cached.refreshing {
+                        kind_stats.in_flight += 1;
+                        loading.push(format!("{:?}", key));
+                    }
+                }
+            }
+        }
+        loading.sort();
+
+        CacheStats {
+            num_entries: data.entries.len(),
+            size_bytes: data.size_bytes,
+            parallelism: self.parallelism.load(Ordering::Relaxed),
+            available_permits: self.semaphore.available_permits(),
+            waiting_for_permit: self.waiting_for_permit.load(Ordering::Relaxed),
+            kinds,
+            loading,
+        }
+    }
+
+    /// Remove the loaded entries that may be affected by a change to the given issue: the issue
+    /// itself, the backlogs, the board issues that contain it and the board issues with the given
+    /// JQL queries (or narrower ones, starting with them), which can contain it after the change
+    pub fn invalidate_issue(&self, key: &str, board_jqls: &[String]) {
+        self.data.lock().retain(|cache_key, value| {
+            let cached = match value {
+                CacheEntry::Loading(_) => return true,
+                CacheEntry::Loaded(cached) => cached,
+            };
+
+            match cache_key {
+                CacheKey::Issue { key: issue_key } | CacheKey::Comments { key: issue_key } => {
+                    issue_key != key
+                }
+                CacheKey::BoardIssues { jql, .. } => {
+                    let contains_issue = match &cached.value {
+                        Err(_) => false,
+                        Ok(value) => match value.as_any().downcast_ref::<BoardIssues>() {
+                            None => false,
+                            Some(issues) => issues.issues.iter().any(|issue| issue.key == key),
+                        },
+                    };
+                    !contains_issue
+                        && !board_jqls
+                            .iter()
+                            .any(|board_jql| jql.starts_with(board_jql.as_str()))
+                }
+                // Any change can move an issue in or out of the backlog
+                CacheKey::Backlog { .. } => false,
+                CacheKey::BoardConfiguration { .. }
+                | CacheKey::Sprints { .. }
+                | CacheKey::Transitions { .. }
+                | CacheKey::DevelopmentInfo { .. }
+                | CacheKey::MergeRequestStatus { .. } => true,
+            }
+        });
+    }
+
+    /// Remove all loaded board issues from the cache, keeping everything else
+    pub fn invalidate_board_issues(&self) {
+        self.data.lock().retain(|cache_key, value| {
+            matches!(value, CacheEntry::Loading(_))
+                || !matches!(
+                    cache_key,
+                    CacheKey::BoardIssues { .. } | CacheKey::Backlog { .. }
+                )
+        });
+    }
+
+    /// Save all successfully loaded entries to disk. If they do not fit in the configured maximum
+    /// size, the ones that expire first are dropped. Entries that cannot be saved are skipped.
+    pub async fn persist(&self) -> Result<()> {
+        let max_size_bytes = match &self.config.persistence {
+            None => return Ok(()),
+            Some(persistence) => persistence.max_size_bytes,
+        };
+
+        // Only take cheap copies while holding the lock, serializing them is done outside of it
+        let now = Instant::now();
+        let snapshot = self
+            .data
             .lock()
-            .retain(|_, value| matches!(value, CacheEntry::Loading(_)));
+            .entries
+            .iter()
+            .filter_map(|(key, entry)| match entry {
+                CacheEntry::Loaded(CachedBox {
+                    live_until,
+                    value: Ok(value),
+                    ..
+                }) => Some((key.clone(), *live_until, value.clone())),
+                _ => None,
+            })
+            .collect_vec();
+
+        let path = self.persistence_path.clone();
+        tokio::task::spawn_blocking(move || {
+            let now_unix = SystemTime::now().duration_since(UNIX_EPOCH)?.as_secs();
+            let mut entries = vec![];
+            for (key, live_until, value) in snapshot {
+                let live_until = if live_until > now {
+                    now_unix + (live_until - now).as_secs()
+                } else {
+                    now_unix.saturating_sub((now - live_until).as_secs())
+                };
+
+                match value.to_json() {
+                    Err(error) => {
+                        tracing::warn!("Not persisting cache entry for {:?}: {:#}", key, error)
+                    }
+                    Ok(value) => entries.push(PersistedEntry {
+                        key,
+                        live_until,
+                        value,
+                    }),
+                }
+            }
+
+            write_persisted_entries(&path, entries, max_size_bytes)
+        })
+        .await?
+    }
+
+    /// Remove the entries that expired and can no longer be served
+    fn sweep(&self) {
+        let now = Instant::now();
+        let mut data = self.data.lock();
+        let len_before = data.entries.len();
+        data.retain(|_, value| match value {
+            CacheEntry::Loading(_) => true,
+            CacheEntry::Loaded(cached) => cached.stale_until >= now,
+        });
+        tracing::debug!(
+            "Swept {} expired cache entries",
+            len_before - data.entries.len()
+        );
+    }
+
+    /// Evict the least recently used entries until the cache fits in the configured capacity
+    fn evict(&self, data: &mut CacheData) {
+        let mut num_evicted = 0;
+        loop {
+            let fits_entries = self
+                .config
+                .max_entries
+                .map_or(true, |max_entries| data.num_loaded <= max_entries);
+            let fits_size = self
+                .config
+                .max_size_bytes
+                .map_or(true, |max_size_bytes| data.size_bytes <= max_size_bytes);
+            if fits_entries && fits_size || !data.remove_least_recently_used() {
+                break;
+            }
+            num_evicted += 1;
+        }
+
+        if num_evicted > 0 {
+            tracing::debug!("Evicted {} cache entries", num_evicted);
+        }
     }
 
+    fn restore(&self) -> Result<()> {
+        let contents = match fs::read(&self.persistence_path) {
+            Err(error) if error.kind() == ErrorKind::NotFound => return Ok(()),
+            contents => contents.context("Could not read cache file")?,
+        };
+        let entries: Vec<PersistedEntry> =
+            serde_json::from_slice(&contents).context("Could not parse cache file")?;
+
+        let now = Instant::now();
+        let now_unix = SystemTime::now().duration_since(UNIX_EPOCH)?.as_secs();
+        let mut data = self.data.lock();
+        for entry in entries {
+            let value = match entry.key.value_from_json(entry.value) {
+                Err(error) => {
+                    tracing::warn!("Ignoring cache entry for {:?}: {}", entry.key, error);
+                    continue;
+                }
+                Ok(value) => value,
+            };
+
+            let live_until = now + Duration::from_secs(entry.live_until.saturating_sub(now_unix));
+            let cached = CachedBox {
+                live_until,
+                stale_until: live_until,
+                restored: true,
+                refreshing: false,
+                generation: 0,
+                size_bytes: value.approximate_size(),
+                value: Ok(value),
+            };
+            data.insert(entry.key, CacheEntry::Loaded(cached));
+        }
+        self.evict(&mut data);
+        tracing::info!(
+            "Restored {} cache entries from {}",
+            data.entries.len(),
+            self.persistence_path.display()
+        );
+
+        Ok(())
+    }
+
+    /// Get the value from the cache, or load it with a single request to Jira
     async fn get<T, G, F>(
         self: &Arc<Self>,
         key: CacheKey,
@@ -139,81 +751,436 @@ impl LocalJiraCache {
         generate: G,
     ) -> Result<T>
     where
-        T: Clone + Send + Sync + 'static,
+        T: Clone + Debug + Serialize + Send + Sync + 'static,
         G: Send + 'static + FnOnce(Arc<JiraApi>) -> F,
         F: Send + Future<Output = Result<T>>,
     {
+        let kind = key.kind();
+        self.get_with(key, time_to_live, move |cache| async move {
+            cache.limited(kind, generate(cache.api.clone())).await
+        })
+        .await
+    }
+
+    /// Get the value from the cache, or load it with `generate`, which must wrap each request to
+    /// Jira in [`Self::limited`]
+    async fn get_with<T, G, F>(
+        self: &Arc<Self>,
+        key: CacheKey,
+        time_to_live: Duration,
+        generate: G,
+    ) -> Result<T>
+    where
+        T: Clone + Debug + Serialize + Send + Sync + 'static,
+        G: Send + 'static + FnOnce(Arc<Self>) -> F,
+        F: Send + Future<Output = Result<T>>,
+    {
+        let kind = key.kind();
+        let count =
+            |update: fn(&mut KindCounters)| update(self.stats.lock().entry(kind).or_default());
+
         loop {
             match self.cache_entry_state::<T>(key.clone()) {
                 CacheEntryState::Miss => {
-                    let inner = self.clone();
-                    let task = tokio::spawn(async move {
-                        let permit = inner.semaphore.acquire().await.unwrap();
-                        let value = generate(inner.api.clone()).await;
-                        drop(permit);
-
-                        let boxed_value = CachedBox::new(time_to_live, value);
-                        let value = boxed_value.get();
-
-                        let old_entry = inner
-                            .data
-                            .lock()
-                            .insert(key, CacheEntry::Loaded(boxed_value));
-                        if let Some(CacheEntry::Loading(notify)) = old_entry {
-                            notify.notify_waiters();
-                            notify.notify_one();
-                        }
-
-                        value
-                    });
-
-                    return task.await.unwrap();
+                    count(|counters| counters.misses += 1);
+                    return self.load(key, time_to_live, generate).await.unwrap();
                 }
                 CacheEntryState::Loading(notify) => {
+                    count(|counters| counters.coalesced += 1);
                     notify.notified().await;
                     notify.notify_one();
                 }
-                CacheEntryState::Hit(value) => return value,
+                CacheEntryState::Hit(value) => {
+                    count(|counters| counters.hits += 1);
+                    return value;
+                }
+                CacheEntryState::Stale(value) => {
+                    count(|counters| counters.stale_hits += 1);
+                    self.load(key, time_to_live, generate);
+                    return value;
+                }
             }
         }
     }
 
+    /// Spawn a task to load the value and store it in the cache
+    fn load<T, G, F>(
+        self: &Arc<Self>,
+        key: CacheKey,
+        time_to_live: Duration,
+        generate: G,
+    ) -> JoinHandle<Result<T>>
+    where
+        T: Clone + Debug + Serialize + Send + Sync + 'static,
+        G: Send + 'static + FnOnce(Arc<Self>) -> F,
+        F: Send + Future<Output = Result<T>>,
+    {
+        let inner = self.clone();
+        tokio::spawn(async move {
+            let value = generate(inner.clone()).await;
+
+            if value.is_err() {
+                inner.stats.lock().entry(key.kind()).or_default().errors += 1;
+            }
+
+            let failure_kind = value.as_ref().err().map(FailureKind::of);
+            let time_to_live = match failure_kind {
+                None => time_to_live,
+                Some(FailureKind::Retryable) => inner
+                    .config
+                    .ttl_retryable_error_seconds
+                    .map_or(time_to_live, Duration::from_secs),
+                Some(FailureKind::Permanent) => inner
+                    .config
+                    .ttl_permanent_error_seconds
+                    .map_or(time_to_live, Duration::from_secs),
+            };
+            let stale_time =
+                Duration::from_secs(inner.config.stale_while_revalidate_seconds.unwrap_or(0));
+            let boxed_value = CachedBox::new(time_to_live, stale_time, value);
+
+            let mut data = inner.data.lock();
+            if let (Some(FailureKind::Retryable), Some(CacheEntry::Loaded(cached))) =
+                (failure_kind, data.entries.get_mut(&key))
+            {
+                // Keep serving the stale value if refreshing it failed for a transient reason
+                if cached.refreshing {
+                    tracing::warn!(
+                        "Failed to refresh {:?}, will keep the stale value for now: {:?}",
+                        key,
+                        boxed_value.value.as_ref().err()
+                    );
+                    cached.refreshing = false;
+                    cached.live_until = boxed_value.live_until;
+                    cached.stale_until = cached.stale_until.max(boxed_value.stale_until);
+                    return boxed_value.get();
+                }
+            }
+
+            let value = boxed_value.get();
+            let old_entry = data.insert(key, CacheEntry::Loaded(boxed_value));
+            inner.evict(&mut data);
+            drop(data);
+            if let Some(CacheEntry::Loading(notify)) = old_entry {
+                notify.notify_waiters();
+                notify.notify_one();
+            }
+
+            value
+        })
+    }
+
+    /// Run a request to Jira once a permit is available, so that at most `parallelism` requests
+    /// run at the same time. Its latency is counted for the given kind, once it got the permit
+    async fn limited<T>(
+        self: &Arc<Self>,
+        kind: &'static str,
+        request: impl Future<Output = T>,
+    ) -> T {
+        self.waiting_for_permit.fetch_add(1, Ordering::Relaxed);
+        let permit = self.semaphore.acquire().await.unwrap();
+        self.waiting_for_permit.fetch_sub(1, Ordering::Relaxed);
+        let value = self.timed(kind, request).await;
+        drop(permit);
+
+        if self.adaptive_parallelism {
+            self.adapt_parallelism();
+        }
+
+        value
+    }
+
+    /// Run a request, counting its latency for the given kind
+    async fn timed<T>(&self, kind: &'static str, request: impl Future<Output = T>) -> T {
+        let start = Instant::now();
+        let value = request.await;
+        let latency = start.elapsed();
+
+        let mut stats = self.stats.lock();
+        let counters = stats.entry(kind).or_default();
+        counters.requests += 1;
+        counters.total_latency += latency;
+
+        value
+    }
+
+    /// Reduce the parallelism when Jira rate limits us and slowly increase it back afterwards
+    fn adapt_parallelism(self: &Arc<Self>) {
+        let current = self.parallelism.load(Ordering::Relaxed);
+
+        if self.api.take_rate_limited() {
+            self.loads_since_rate_limited.store(0, Ordering::Relaxed);
+            if current > 1
+                && self
+                    .parallelism
+                    .compare_exchange(current, current - 1, Ordering::Relaxed, Ordering::Relaxed)
+                    .is_ok()
+            {
+                tracing::warn!(
+                    "Rate limited by Jira, reducing parallelism to {}",
+                    current - 1
+                );
+                let inner = self.clone();
+                tokio::spawn(async move {
+                    inner.semaphore.acquire().await.unwrap().forget();
+                });
+            }
+        } else if current < self.max_parallelism
+            && self
+                .loads_since_rate_limited
+                .fetch_add(1, Ordering::Relaxed)
+                + 1
+                >= LOADS_TO_INCREASE_PARALLELISM
+            && self
+                .parallelism
+                .compare_exchange(current, current + 1, Ordering::Relaxed, Ordering::Relaxed)
+                .is_ok()
+        {
+            tracing::info!("Increasing parallelism back to {}", current + 1);
+            self.loads_since_rate_limited.store(0, Ordering::Relaxed);
+            self.semaphore.add_permits(1);
+        }
+    }
+
     fn cache_entry_state<T: Clone + 'static>(&self, key: CacheKey) -> CacheEntryState<T> {
         let mut data = self.data.lock();
-        match data.entry(key) {
-            Entry::Vacant(vacant) => {
-                let notify = Arc::new(Notify::new());
-                vacant.insert(CacheEntry::Loading(notify));
-                CacheEntryState::Miss
-            }
-            Entry::Occupied(mut occupied) => match occupied.get() {
-                CacheEntry::Loading(notify) => CacheEntryState::Loading(notify.clone()),
-                CacheEntry::Loaded(cached) => {
-                    if cached.live_until < Instant::now() {
-                        let notify = Arc::new(Notify::new());
-                        occupied.insert(CacheEntry::Loading(notify));
-                        CacheEntryState::Miss
-                    } else {
+        let state = match data.entries.get_mut(&key) {
+            None => CacheEntryState::Miss,
+            Some(CacheEntry::Loading(notify)) => return CacheEntryState::Loading(notify.clone()),
+            Some(CacheEntry::Loaded(cached)) => {
+                let now = Instant::now();
+                if cached.live_until >= now {
+                    CacheEntryState::Hit(cached.get())
+                } else if cached.can_serve_stale(now) {
+                    if cached.refreshing {
                         CacheEntryState::Hit(cached.get())
+                    } else {
+                        cached.refreshing = true;
+                        CacheEntryState::Stale(cached.get())
                     }
+                } else {
+                    CacheEntryState::Miss
                 }
-            },
+            }
+        };
+
+        match state {
+            CacheEntryState::Miss => {
+                let notify = Arc::new(Notify::new());
+                data.insert(key, CacheEntry::Loading(notify));
+            }
+            _ => data.touch(key),
         }
+
+        state
+    }
+}
+
+impl CacheData {
+    /// Insert or replace an entry, which counts as using it
+    fn insert(&mut self, key: CacheKey, mut entry: CacheEntry) -> Option<CacheEntry> {
+        if let CacheEntry::Loaded(cached) = &mut entry {
+            cached.generation = self.next_generation();
+            self.usage.push_back((cached.generation, key.clone()));
+            self.num_loaded += 1;
+            self.size_bytes += cached.size_bytes;
+        }
+
+        let old_entry = self.entries.insert(key, entry);
+        if let Some(CacheEntry::Loaded(old_cached)) = &old_entry {
+            self.num_loaded -= 1;
+            self.size_bytes -= old_cached.size_bytes;
+        }
+        self.compact_usage();
+
+        old_entry
+    }
+
+    /// Mark a loaded entry as the most recently used
+    fn touch(&mut self, key: CacheKey) {
+        let generation = self.next_generation();
+        if let Some(CacheEntry::Loaded(cached)) = self.entries.get_mut(&key) {
+            cached.generation = generation;
+            self.usage.push_back((generation, key));
+            self.compact_usage();
+        }
+    }
+
+    fn retain(&mut self, mut keep: impl FnMut(&CacheKey, &mut CacheEntry) -> bool) {
+        let CacheData {
+            entries,
+            num_loaded,
+            size_bytes,
+            ..
+        } = self;
+        entries.retain(|key, entry| {
+            let kept = keep(key, entry);
+            if let (false, CacheEntry::Loaded(cached)) = (kept, entry) {
+                *num_loaded -= 1;
+                *size_bytes -= cached.size_bytes;
+            }
+            kept
+        });
+    }
+
+    /// Remove the loaded entry that was used the longest time ago, returning whether there was one
+    fn remove_least_recently_used(&mut self) -> bool {
+        while let Some((generation, key)) = self.usage.pop_front() {
+            if self.is_current(generation, &key) {
+                if let Some(CacheEntry::Loaded(cached)) = self.entries.remove(&key) {
+                    self.num_loaded -= 1;
+                    self.size_bytes -= cached.size_bytes;
+                }
+                return true;
+            }
+        }
+
+        false
+    }
+
+    fn is_current(&self, generation: u64, key: &CacheKey) -> bool {
+        matches!(
+            self.entries.get(key),
+            Some(CacheEntry::Loaded(cached)) if cached.generation == generation
+        )
+    }
+
+    fn next_generation(&mut self) -> u64 {
+        self.next_generation += 1;
+        self.next_generation
+    }
+
+    /// Drop the outdated copies from the usage queue once they are the majority, so that each
+    /// insertion or use costs a constant amortized time
+    fn compact_usage(&mut self) {
+        if self.usage.len() > 2 * self.num_loaded + 16 {
+            let mut usage = std::mem::take(&mut self.usage);
+            usage.retain(|(generation, key)| self.is_current(*generation, key));
+            self.usage = usage;
+        }
+    }
+}
+
+/// Write the entries to the file, replacing it at once so that it is never left half written
+fn write_persisted_entries(
+    path: &Path,
+    mut entries: Vec<PersistedEntry>,
+    max_size_bytes: usize,
+) -> Result<()> {
+    entries.sort_by_key(|entry| std::cmp::Reverse(entry.live_until));
+    let mut size = 0;
+    let mut num_kept = 0;
+    for entry in &entries {
+        size += serde_json::to_vec(entry)?.len();
+        if size > max_size_bytes {
+            break;
+        }
+        num_kept += 1;
+    }
+    if num_kept < entries.len() {
+        tracing::info!(
+            "Dropping {} entries to respect the cache size limit",
+            entries.len() - num_kept
+        );
+        entries.truncate(num_kept);
+    }
+
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    let temp_path = path.with_extension("json.tmp");
+    fs::write(&temp_path, serde_json::to_vec(&entries)?)?;
+    fs::rename(&temp_path, path)?;
+    tracing::debug!(
+        "Persisted {} cache entries to {}",
+        entries.len(),
+        path.display()
+    );
+
+    Ok(())
+}
+
+impl CacheKey {
+    fn kind(&self) -> &'static str {
+        match self {
+            CacheKey::BoardConfiguration { .. } => "board_configuration",
+            CacheKey::Sprints { .. } => "sprints",
+            CacheKey::Backlog { .. } => "backlog",
+            CacheKey::BoardIssues { .. } => "board_issues",
+            CacheKey::Issue { .. } => "issue",
+            CacheKey::Comments { .. } => "comments",
+            CacheKey::Transitions { .. } => "transitions",
+            CacheKey::DevelopmentInfo { .. } => "development_info",
+            CacheKey::MergeRequestStatus { .. } => "merge_request_status",
+        }
+    }
+
+    /// Parse a value that was previously persisted to disk for this key
+    fn value_from_json(&self, value: Value) -> Result<Arc<dyn CachedValue>> {
+        fn parse<T: CachedValue + DeserializeOwned>(value: Value) -> Result<Arc<dyn CachedValue>> {
+            Ok(Arc::new(serde_json::from_value::<T>(value)?))
+        }
+
+        match self {
+            CacheKey::BoardConfiguration { .. } => parse::<BoardConfiguration>(value),
+            CacheKey::Sprints { .. } => parse::<Vec<Sprint>>(value),
+            CacheKey::Backlog { .. } => parse::<BoardIssues>(value),
+            CacheKey::BoardIssues { .. } => parse::<BoardIssues>(value),
+            CacheKey::Issue { .. } => parse::<Issue>(value),
+            CacheKey::Comments { .. } => parse::<Vec<Comment>>(value),
+            CacheKey::Transitions { .. } => parse::<Vec<Transition>>(value),
+            CacheKey::DevelopmentInfo { .. } => parse::<DevelopmentInfo>(value),
+            CacheKey::MergeRequestStatus { .. } => parse::<MergeRequestStatus>(value),
+        }
+    }
+}
+
+impl<T: Any + Debug + Serialize + Send + Sync> CachedValue for T {
+    fn as_any(&self) -> &dyn Any {
+        self
+    }
+
+    fn to_json(&self) -> Result<Value> {
+        Ok(serde_json::to_value(self)?)
+    }
+
+    fn approximate_size(&self) -> usize {
+        serde_json::to_vec(self).map_or(0, |bytes| bytes.len())
     }
 }
 
 impl CachedBox {
-    fn new<T: Send + Sync + 'static>(time_to_live: Duration, cached: Result<T>) -> CachedBox {
+    fn new<T: CachedValue>(
+        time_to_live: Duration,
+        stale_time: Duration,
+        cached: Result<T>,
+    ) -> CachedBox {
+        let now = Instant::now();
+        let live_until = now + time_to_live;
         CachedBox {
-            live_until: Instant::now() + time_to_live,
-            value: cached.map(|value| Box::new(value) as Box<dyn Any + Send + Sync>),
+            live_until,
+            stale_until: live_until + stale_time,
+            restored: false,
+            refreshing: false,
+            generation: 0,
+            size_bytes: cached.as_ref().map_or(0, |value| value.approximate_size()),
+            value: cached
+                .map(|value| Arc::new(value) as Arc<dyn CachedValue>)
+                .map_err(Arc::new),
         }
     }
 
+    /// Whether this expired value can still be returned while a fresh one is loaded. Values
+    /// restored from disk are always good enough to be displayed at startup.
+    fn can_serve_stale(&self, now: Instant) -> bool {
+        self.value.is_ok() && (self.restored || now < self.stale_until)
+    }
+
     fn get<T: Clone + 'static>(&self) -> Result<T> {
         let value = match &self.value {
-            Err(error) => Err(anyhow!("{:?}", error)),
+            Err(error) => Err(Error::new(SharedError::new(error.clone()))),
             Ok(boxed_value) => boxed_value
+                .as_any()
                 .downcast_ref::<T>()
                 .context("failed to downcast to desired type")
                 .cloned(),
@@ -222,3 +1189,215 @@ impl CachedBox {
         value
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::Config;
+    use crate::jira_api::JiraError;
+
+    #[test]
+    fn serve_stale_values() {
+        let now = Instant::now();
+        let cached = CachedBox::new(Duration::ZERO, Duration::from_secs(60), Ok(17));
+        assert!(cached.can_serve_stale(now));
+        assert!(!cached.can_serve_stale(now + Duration::from_secs(61)));
+
+        let failed = CachedBox::new::<i32>(
+            Duration::ZERO,
+            Duration::from_secs(60),
+            Err(anyhow::anyhow!("")),
+        );
+        assert!(!failed.can_serve_stale(now));
+    }
+
+    #[test]
+    fn evict_least_recently_used() {
+        let key = |key: &str| CacheKey::Issue {
+            key: key.to_owned(),
+        };
+        let loaded = || CacheEntry::Loaded(CachedBox::new(Duration::ZERO, Duration::ZERO, Ok(17)));
+
+        let mut data = CacheData::default();
+        data.insert(key("A"), loaded());
+        data.insert(key("B"), loaded());
+        data.insert(key("C"), loaded());
+        data.touch(key("A"));
+        data.insert(key("B"), loaded());
+        for _ in 0..100 {
+            data.touch(key("C"));
+        }
+        assert!(data.usage.len() <= 2 * data.num_loaded + 16);
+
+        for evicted in ["A", "B", "C"] {
+            assert!(data.remove_least_recently_used());
+            assert!(!data.entries.contains_key(&key(evicted)));
+        }
+        assert_eq!(data.num_loaded, 0);
+        assert_eq!(data.size_bytes, 0);
+        assert!(!data.remove_least_recently_used());
+    }
+
+    #[tokio::test]
+    async fn persist_and_restore() {
+        let config: Config =
+            toml::from_str(include_str!("../resources/default_config.toml")).unwrap();
+        let project_dirs = ProjectDirs::from_path(
+            std::env::temp_dir().join(format!("kaiju-test-persist-{}", std::process::id())),
+        )
+        .unwrap();
+        let new_cache = || {
+            Arc::new(LocalJiraCache::new(
+                Arc::new(JiraApi::new(&config)),
+                1,
+                false,
+                config.cache.clone(),
+                &project_dirs,
+            ))
+        };
+
+        let cache = new_cache();
+        let issue = Issue {
+            id: "10017".to_owned(),
+            key: "WEB-17".to_owned(),
+            fields: serde_json::json!({"summary": "Persist me"}),
+        };
+        // Keys must be strings in JSON, so this entry cannot be saved
+        let unsaveable = BTreeMap::from([((1, 2), 3)]);
+        {
+            let mut data = cache.data.lock();
+            let time_to_live = Duration::from_secs(3600);
+            data.insert(
+                CacheKey::Issue {
+                    key: issue.key.clone(),
+                },
+                CacheEntry::Loaded(CachedBox::new(time_to_live, Duration::ZERO, Ok(issue))),
+            );
+            data.insert(
+                CacheKey::Comments {
+                    key: "WEB-17".to_owned(),
+                },
+                CacheEntry::Loaded(CachedBox::new(time_to_live, Duration::ZERO, Ok(unsaveable))),
+            );
+        }
+        cache.persist().await.unwrap();
+
+        let restored = new_cache();
+        fs::remove_dir_all(project_dirs.project_path()).unwrap();
+        assert_eq!(restored.data.lock().entries.len(), 1);
+        let issue = restored.issue("WEB-17".to_owned()).await.unwrap();
+        assert_eq!(issue.fields["summary"], "Persist me");
+    }
+
+    #[tokio::test]
+    async fn keep_stale_values_after_retryable_errors() {
+        let config: Config =
+            toml::from_str(include_str!("../resources/default_config.toml")).unwrap();
+        let mut cache_config = config.cache.clone();
+        cache_config.persistence = None;
+        let cache = Arc::new(LocalJiraCache::new(
+            Arc::new(JiraApi::new(&config)),
+            1,
+            false,
+            cache_config,
+            &ProjectDirs::from_path(std::env::temp_dir()).unwrap(),
+        ));
+        let key = CacheKey::Comments {
+            key: "WEB-17".to_owned(),
+        };
+        let mut stale = CachedBox::new(Duration::ZERO, Duration::ZERO, Ok(17));
+        stale.refreshing = true;
+        cache
+            .data
+            .lock()
+            .insert(key.clone(), CacheEntry::Loaded(stale));
+
+        let unavailable = JiraError {
+            status: reqwest::StatusCode::SERVICE_UNAVAILABLE,
+            messages: vec![],
+            field_errors: BTreeMap::new(),
+        };
+        let result = cache
+            .load(key.clone(), Duration::ZERO, |_| async move {
+                Err::<i32, _>(Error::new(unavailable))
+            })
+            .await
+            .unwrap();
+        assert!(result.is_err());
+
+        // The stale value is served again for as long as the error is cached, and then some
+        let mut data = cache.data.lock();
+        let Some(CacheEntry::Loaded(cached)) = data.entries.get_mut(&key) else {
+            panic!("The stale value was dropped");
+        };
+        assert!(cached.can_serve_stale(cached.live_until));
+        assert_eq!(cached.get::<i32>().unwrap(), 17);
+    }
+
+    #[test]
+    fn invalidate_filtered_columns() {
+        let config: Config =
+            toml::from_str(include_str!("../resources/default_config.toml")).unwrap();
+        let mut cache_config = config.cache.clone();
+        cache_config.persistence = None;
+        let cache = LocalJiraCache::new(
+            Arc::new(JiraApi::new(&config)),
+            1,
+            false,
+            cache_config,
+            &ProjectDirs::from_path(std::env::temp_dir()).unwrap(),
+        );
+        let key = |jql: &str| CacheKey::BoardIssues {
+            id: "1".to_owned(),
+            fields: String::new(),
+            jql: jql.to_owned(),
+            max_issues: None,
+        };
+        let empty = || {
+            CacheEntry::Loaded(CachedBox::new(
+                Duration::from_secs(60),
+                Duration::ZERO,
+                Ok(BoardIssues {
+                    issues: vec![],
+                    total: 0,
+                }),
+            ))
+        };
+        for jql in [
+            "status in (1)",
+            "status in (1) and assignee = currentUser()",
+            "status in (2)",
+        ] {
+            cache.data.lock().insert(key(jql), empty());
+        }
+
+        cache.invalidate_issue("WEB-17", &["status in (1) and ".to_owned()]);
+
+        let data = cache.data.lock();
+        assert!(data.entries.contains_key(&key("status in (1)")));
+        assert!(!data
+            .entries
+            .contains_key(&key("status in (1) and assignee = currentUser()")));
+        assert!(data.entries.contains_key(&key("status in (2)")));
+    }
+
+    #[test]
+    fn shared_errors_keep_their_chain() {
+        let jira_error = JiraError {
+            status: reqwest::StatusCode::NOT_FOUND,
+            messages: vec!["Issue does not exist".to_owned()],
+            field_errors: BTreeMap::new(),
+        };
+        let cached =
+            CachedBox::new::<i32>(Duration::ZERO, Duration::ZERO, Err(Error::new(jira_error)));
+
+        let error = cached.get::<i32>().unwrap_err();
+        assert!(error
+            .chain()
+            .any(|cause| cause.downcast_ref::<JiraError>().is_some()));
+        assert_eq!(
+            format!("{:#}", error),
+            "Cached error: Jira answered 404 Not Found (Issue does not exist)"
+        );
+    }
+}
@@ -2,14 +2,18 @@ mod ask_user_edit;
 mod board;
 mod commands;
 mod config;
+mod forge_api;
 mod issue_code;
 mod jira_api;
 mod local_jira_cache;
 
-use crate::commands::{create_issue, edit_config, open_board};
+use crate::commands::{
+    cache_stats, create_issue, create_issues, edit_config, edit_issue, log_work, open_board,
+};
 use anyhow::{Context, Result};
 use clap::{Parser, Subcommand};
 use directories::ProjectDirs;
+use std::path::PathBuf;
 
 #[derive(Debug, Parser)]
 #[clap(author, version, about)]
@@ -22,8 +26,23 @@ struct Args {
 enum Command {
     /// Edit the configurations for Kaiju
     EditConfig,
-    /// Create a new issue
-    CreateIssue,
+    /// Create a new issue, written in the editor unless given in the options
+    CreateIssue(create_issue::CreateIssueOptions),
+    /// Create many issues at once, from a document where they are separated by lines with
+    /// "<!-- kaiju:next-issue -->"
+    CreateIssues {
+        /// The file with the issues. If not given, they are written in the editor
+        file: Option<PathBuf>,
+    },
+    /// Edit an existing issue, in the editor
+    EditIssue {
+        /// The key of the issue, like "WEB-123"
+        key: String,
+        /// The name of the board whose sprints the issue can be moved to, as defined in the config
+        /// file
+        #[clap(long)]
+        board: Option<String>,
+    },
     /// Open the Web interface in a browser
     OpenBoard {
         /// The name of the board, as defined in the config file
@@ -36,11 +55,25 @@ enum Command {
         #[clap(long)]
         dev_mode: bool,
     },
+    /// Show the statistics of the Jira cache of a running board
+    CacheStats,
+    /// Log time spent working on an issue
+    LogWork {
+        /// The key of the issue, like "WEB-123"
+        key: String,
+        /// The time spent, like "1h30m" or "2d"
+        duration: String,
+        /// What was done
+        #[clap(long)]
+        comment: Option<String>,
+    },
 }
 
 #[tokio::main]
 async fn main() -> Result<()> {
-    tracing_subscriber::fmt::init();
+    tracing_subscriber::fmt()
+        .with_writer(std::io::stderr)
+        .init();
 
     let project_dirs = ProjectDirs::from("", "sitegui", "kaiju")
         .context("Could not determine local configuration directory")?;
@@ -49,11 +82,23 @@ async fn main() -> Result<()> {
 
     match args.command {
         Command::EditConfig => edit_config::edit_config(&project_dirs),
-        Command::CreateIssue => create_issue::create_issue(&project_dirs).await,
+        Command::CreateIssue(options) => create_issue::create_issue(&project_dirs, options).await,
+        Command::CreateIssues { file } => {
+            create_issues::create_issues(&project_dirs, file.as_deref()).await
+        }
+        Command::EditIssue { key, board } => {
+            edit_issue::edit_issue(&project_dirs, &key, board.as_deref()).await
+        }
         Command::OpenBoard {
             board_name,
             no_browser,
             dev_mode,
         } => open_board::open_board(&project_dirs, &board_name, no_browser, dev_mode).await,
+        Command::CacheStats => cache_stats::cache_stats(&project_dirs).await,
+        Command::LogWork {
+            key,
+            duration,
+            comment,
+        } => log_work::log_work(&project_dirs, &key, &duration, comment).await,
     }
 }